@@ -0,0 +1,219 @@
+use crate::{Note, NoteEvent, Rng, Sequence, TempoMap};
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum ArpMode {
+    Up,
+    Down,
+    /// Up then back down, without repeating the top and bottom notes
+    UpDown,
+    Random,
+    /// In the order the notes were pressed
+    AsPlayed,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Arpeggiator {
+    pub mode: ArpMode,
+    /// Number of octaves the pattern spans, 1 plays only the held notes
+    pub octaves: u32,
+    /// Note value of each step: 4 for quarters, 8 for eighths, 16 for sixteenths
+    pub rate: u32,
+    pub triplets: bool,
+    /// Fraction of a step each note is held for
+    pub gate: f32,
+    /// Keeps playing the last chord after its keys are released
+    pub latch: bool,
+    pub velocity: f32,
+    pub seed: u64,
+}
+
+impl Default for Arpeggiator {
+    fn default() -> Arpeggiator {
+        Arpeggiator {
+            mode: ArpMode::Up,
+            octaves: 1,
+            rate: 16,
+            triplets: false,
+            gate: 0.5,
+            latch: false,
+            velocity: 0.8,
+            seed: 0,
+        }
+    }
+}
+
+impl Arpeggiator {
+    pub fn new(mode: ArpMode) -> Arpeggiator {
+        Arpeggiator {
+            mode,
+            ..Arpeggiator::default()
+        }
+    }
+
+    /// # Panics
+    /// Panics if `octaves` is 0
+    #[track_caller]
+    pub fn with_octaves(mut self, octaves: u32) -> Arpeggiator {
+        assert!(octaves > 0, "An arpeggio needs at least one octave");
+        self.octaves = octaves;
+        self
+    }
+
+    /// # Panics
+    /// Panics if `rate` is 0
+    #[track_caller]
+    pub fn with_rate(mut self, rate: u32, triplets: bool) -> Arpeggiator {
+        assert!(rate > 0, "Invalid note division");
+        self.rate = rate;
+        self.triplets = triplets;
+        self
+    }
+
+    pub fn with_gate(mut self, gate: f32) -> Arpeggiator {
+        self.gate = gate;
+        self
+    }
+
+    pub fn with_latch(mut self, latch: bool) -> Arpeggiator {
+        self.latch = latch;
+        self
+    }
+
+    pub fn with_velocity(mut self, velocity: f32) -> Arpeggiator {
+        self.velocity = velocity;
+        self
+    }
+
+    pub fn with_seed(mut self, seed: u64) -> Arpeggiator {
+        self.seed = seed;
+        self
+    }
+
+    /// Length of one step in quarter notes
+    pub fn step(&self) -> f64 {
+        let step = 4.0 / self.rate as f64;
+        if self.triplets {
+            step * 2.0 / 3.0
+        } else {
+            step
+        }
+    }
+
+    /// One cycle of the pattern for `chord`, given in the order it was played.
+    /// [`ArpMode::Random`] gives the notes to pick from
+    pub fn cycle(&self, chord: &[Note]) -> Vec<Note> {
+        let mut notes: Vec<Note> = Vec::new();
+        for note in chord {
+            if !notes.contains(note) {
+                notes.push(*note);
+            }
+        }
+        if self.mode != ArpMode::AsPlayed {
+            notes.sort_by(|a, b| a.frequency().total_cmp(&b.frequency()));
+        }
+        // Notes pushed above the highest octave are dropped
+        let mut cycle: Vec<Note> = (0..self.octaves)
+            .flat_map(|octave| {
+                notes
+                    .iter()
+                    .filter(move |note| note.octave + octave <= 8)
+                    .map(move |note| Note::new(note.note, note.octave + octave))
+            })
+            .collect();
+        match self.mode {
+            ArpMode::Down => cycle.reverse(),
+            ArpMode::UpDown if cycle.len() > 2 => {
+                let down: Vec<Note> = cycle[1..cycle.len() - 1].iter().rev().copied().collect();
+                cycle.extend(down);
+            }
+            _ => {}
+        }
+        cycle
+    }
+
+    /// Arpeggiates `chord` held down for `quarters` quarter notes from the start
+    pub fn chord(&self, chord: &[Note], tempo: &TempoMap, quarters: f64) -> Sequence {
+        let held = tempo.seconds_at_quarters(quarters);
+        let input = Sequence::from_events(
+            chord
+                .iter()
+                .map(|&note| NoteEvent::new(note, 0.0, held, self.velocity))
+                .collect(),
+        );
+        self.arpeggiate(&input, tempo, held)
+    }
+
+    /// Arpeggiates the notes held in `input` on the step grid of `tempo`, stopping at `until` seconds,
+    /// or when the last key is let go if not latched.
+    /// Pressing a key after letting go of all of them starts a new chord, even when latched
+    /// # Panics
+    /// Panics if `until` is not finite
+    #[track_caller]
+    pub fn arpeggiate(&self, input: &Sequence, tempo: &TempoMap, until: f64) -> Sequence {
+        assert!(until.is_finite(), "An arpeggio needs a finite end");
+        let end = if self.latch {
+            until
+        } else {
+            until.min(input.duration())
+        };
+        let mut held: Vec<NoteEvent> = input.events().to_vec();
+        held.sort_by(|a, b| a.start.total_cmp(&b.start));
+        let mut rng = Rng::new(self.seed);
+        let mut output = Sequence::new();
+        let mut latched: Vec<Note> = Vec::new();
+        let mut pressed: Vec<Note> = Vec::new();
+        let mut cycle: Vec<Note> = Vec::new();
+        let mut index = 0;
+        let step = self.step();
+        let steps = (tempo.quarters_at_seconds(end.max(0.0)) / step).ceil() as usize;
+
+        for i in 0..steps {
+            let start = tempo.seconds_at_quarters(i as f64 * step);
+            if start >= end {
+                break;
+            }
+            let now: Vec<Note> = held
+                .iter()
+                .filter(|e| e.start <= start && start < e.end())
+                .map(|e| e.note)
+                .collect();
+            if !now.is_empty() && pressed.is_empty() {
+                latched.clear();
+                index = 0;
+            }
+            for note in &now {
+                if !latched.contains(note) {
+                    latched.push(*note);
+                }
+            }
+            if !self.latch {
+                latched.retain(|note| now.contains(note));
+            }
+            pressed = now;
+
+            let next = self.cycle(&latched);
+            if next != cycle {
+                cycle = next;
+                if !cycle.is_empty() {
+                    index %= cycle.len();
+                }
+            }
+            if cycle.is_empty() {
+                continue;
+            }
+            let note = match self.mode {
+                ArpMode::Random => {
+                    cycle[(rng.next_f32() * cycle.len() as f32) as usize % cycle.len()]
+                }
+                _ => {
+                    let note = cycle[index];
+                    index = (index + 1) % cycle.len();
+                    note
+                }
+            };
+            let end = tempo.seconds_at_quarters((i as f64 + self.gate as f64) * step);
+            output.add_note(note, start, end - start, self.velocity);
+        }
+        output
+    }
+}
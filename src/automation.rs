@@ -0,0 +1,279 @@
+use crate::{mixer::PanLaw, Channel, Filter, FilterType, ModulatedWave, SampleType, Samples, Wave};
+
+/// How the value travels to a breakpoint from the one before it
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum CurveShape {
+    Linear,
+    /// Constant ratio per second, for pitch, cutoff and fades.
+    /// Falls back to linear when either end is 0 or they differ in sign
+    Exponential,
+    /// Stays at the previous value, then jumps on the breakpoint
+    Hold,
+}
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Breakpoint {
+    /// In seconds
+    pub time: f32,
+    pub value: f32,
+    /// Shape of the segment that ends here
+    pub shape: CurveShape,
+}
+
+/// A breakpoint curve over time. Before the first breakpoint and after the last one the
+/// value stays at that breakpoint's
+#[derive(Clone, Debug, PartialEq)]
+pub struct Automation {
+    points: Vec<Breakpoint>,
+}
+
+impl Automation {
+    /// A curve starting at `value`
+    pub fn new(value: f32) -> Automation {
+        Automation {
+            points: vec![Breakpoint {
+                time: 0.0,
+                value,
+                shape: CurveShape::Linear,
+            }],
+        }
+    }
+
+    pub fn from_points(mut points: Vec<Breakpoint>) -> Automation {
+        points.sort_by(|a, b| a.time.total_cmp(&b.time));
+        Automation { points }
+    }
+
+    /// Adds a breakpoint, replacing any already at `time`
+    pub fn add(&mut self, time: f32, value: f32, shape: CurveShape) {
+        let point = Breakpoint { time, value, shape };
+        match self.points.binary_search_by(|p| p.time.total_cmp(&time)) {
+            Ok(i) => self.points[i] = point,
+            Err(i) => self.points.insert(i, point),
+        }
+    }
+
+    pub fn then(mut self, time: f32, value: f32, shape: CurveShape) -> Automation {
+        self.add(time, value, shape);
+        self
+    }
+
+    pub fn points(&self) -> &[Breakpoint] {
+        &self.points
+    }
+
+    /// Time of the last breakpoint
+    pub fn duration(&self) -> f32 {
+        self.points.last().map_or(0.0, |p| p.time)
+    }
+
+    pub fn at(&self, time: f32) -> f32 {
+        let next = self.points.partition_point(|p| p.time <= time);
+        self.segment(next, time)
+    }
+
+    /// The curve at every sample of the first `seconds`
+    pub fn values(&self, sample_rate: u32, seconds: f32) -> Vec<f32> {
+        let count = (sample_rate as f32 * seconds) as usize;
+        let mut next = 0;
+        (0..count)
+            .map(|i| {
+                let time = i as f32 / sample_rate as f32;
+                while next < self.points.len() && self.points[next].time <= time {
+                    next += 1;
+                }
+                self.segment(next, time)
+            })
+            .collect()
+    }
+
+    /// Value at `time`, given the index of the first breakpoint after it
+    fn segment(&self, next: usize, time: f32) -> f32 {
+        let from = next.checked_sub(1).map(|i| self.points[i]);
+        match (from, self.points.get(next)) {
+            (Some(from), Some(&to)) => interpolate(from, to, time),
+            (Some(point), None) | (None, Some(&point)) => point.value,
+            (None, None) => 0.0,
+        }
+    }
+
+    /// Scales the first `seconds` of `samples` by the curve, as a linear gain
+    pub fn apply_gain(&self, samples: &Samples, seconds: f32) -> Samples {
+        let sample_rate = samples.sample_rate();
+        let points = self
+            .values(sample_rate, seconds)
+            .into_iter()
+            .enumerate()
+            .map_while(|(i, gain)| samples.sample_f32(i).map(|x| x * gain))
+            .collect();
+        Samples::new(SampleType::Pointsf32(points)).with_sample_rate(sample_rate)
+    }
+
+    /// Filters `samples` with the cutoff following the curve, in Hz
+    pub fn apply_cutoff(&self, filter: &mut Filter, samples: &Samples, seconds: f32) -> Samples {
+        filter.process_samples_with(samples, seconds, |time| Some(self.at(time)))
+    }
+}
+
+fn interpolate(from: Breakpoint, to: Breakpoint, time: f32) -> f32 {
+    let x = (time - from.time) / (to.time - from.time);
+    match to.shape {
+        CurveShape::Hold => from.value,
+        CurveShape::Exponential if from.value * to.value > 0.0 => {
+            from.value * (to.value / from.value).powf(x)
+        }
+        _ => from.value + (to.value - from.value) * x,
+    }
+}
+
+/// A wave with automation lanes on its amplitude, pitch, stereo position and filter cutoff
+#[derive(Clone, Debug, PartialEq)]
+pub struct AutomatedWave {
+    /// The wave and any LFOs, applied on top of the automation
+    pub modulation: ModulatedWave,
+    /// Linear gain
+    pub amplitude: Option<Automation>,
+    /// Ratio to the wave's own frequencies, so 2 is an octave up
+    pub frequency: Option<Automation>,
+    /// From -1 (left) to 1 (right)
+    pub pan: Option<Automation>,
+    /// Butterworth filter with its cutoff in Hz
+    pub filter: Option<(FilterType, u32, Automation)>,
+}
+
+impl From<ModulatedWave> for AutomatedWave {
+    fn from(modulation: ModulatedWave) -> AutomatedWave {
+        AutomatedWave {
+            modulation,
+            amplitude: None,
+            frequency: None,
+            pan: None,
+            filter: None,
+        }
+    }
+}
+
+impl AutomatedWave {
+    pub fn new(wave: Wave) -> AutomatedWave {
+        AutomatedWave::from(ModulatedWave::new(wave))
+    }
+
+    pub fn with_amplitude(mut self, automation: Automation) -> AutomatedWave {
+        self.amplitude = Some(automation);
+        self
+    }
+
+    pub fn with_frequency(mut self, automation: Automation) -> AutomatedWave {
+        self.frequency = Some(automation);
+        self
+    }
+
+    pub fn with_pan(mut self, automation: Automation, pan_law: PanLaw) -> AutomatedWave {
+        self.pan = Some(automation);
+        self.modulation.pan_law = pan_law;
+        self
+    }
+
+    /// # Panics
+    /// Panics if `kind` is not `LowPass` or `HighPass`, or `order` is 0
+    #[track_caller]
+    pub fn with_filter(
+        mut self,
+        kind: FilterType,
+        order: u32,
+        cutoff: Automation,
+    ) -> AutomatedWave {
+        assert!(
+            matches!(kind, FilterType::LowPass | FilterType::HighPass),
+            "Butterworth filters are only low or high pass"
+        );
+        assert!(order > 0, "Filter order must be at least 1");
+        self.filter = Some((kind, order, cutoff));
+        self
+    }
+
+    /// Renders the wave, returning a stereo channel only if pan is automated or auto-pan is set
+    pub fn render(&self, sample_rate: u32, seconds: f32) -> Channel {
+        let lfos = &self.modulation;
+        let values = |automation: &Option<Automation>| {
+            automation.as_ref().map(|a| a.values(sample_rate, seconds))
+        };
+        let gains = values(&self.amplitude);
+        let ratios = values(&self.frequency);
+        let mut filter = self.filter.as_ref().map(|(kind, order, cutoff)| {
+            let cutoffs = cutoff.values(sample_rate, seconds);
+            let start = cutoffs.first().copied().unwrap_or(cutoff.at(0.0));
+            (
+                Filter::butterworth(*kind, *order, start, sample_rate),
+                cutoffs,
+            )
+        });
+
+        // A frequency ratio shared by every partial is the same as warping time. The warped time
+        // is summed in f64, as f32 rounds away part of every step after a few seconds
+        let pitched = ratios.is_some() || lfos.vibrato.is_some();
+        let mut warped = 0.0f64;
+        let count = (sample_rate as f32 * seconds) as usize;
+        let mono: Vec<f32> = (0..count)
+            .map(|i| {
+                let time = i as f64 / sample_rate as f64;
+                let x = time as f32;
+                let mut value = if pitched {
+                    let value = lfos.wave.at(warped as f32);
+                    let ratio = ratios.as_ref().map_or(1.0, |r| r[i]) * lfos.ratio_at(x);
+                    warped += ratio as f64 / sample_rate as f64;
+                    value
+                } else {
+                    lfos.wave.at(x)
+                };
+                if let Some((filter, cutoffs)) = &mut filter {
+                    filter.set_cutoff(cutoffs[i]);
+                    value = filter.process(value);
+                }
+                value * gains.as_ref().map_or(1.0, |g| g[i]) * lfos.gain_at(x)
+            })
+            .collect();
+
+        let samples =
+            |points| Samples::new(SampleType::Pointsf32(points)).with_sample_rate(sample_rate);
+        let pans = values(&self.pan);
+        if pans.is_none() && lfos.auto_pan.is_none() {
+            return Channel::Mono(samples(mono));
+        }
+        let (right, left) = mono
+            .iter()
+            .enumerate()
+            .map(|(i, &value)| {
+                let pan = pans.as_ref().map_or(0.0, |p| p[i])
+                    + lfos.pan_at((i as f64 / sample_rate as f64) as f32);
+                let (right, left) = lfos.pan_law.gains(pan);
+                (value * right, value * left)
+            })
+            .unzip();
+        Channel::Stereo(samples(right), samples(left))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::SineWave;
+    use std::f64::consts::{LN_2, TAU};
+
+    #[test]
+    fn frequency_automation_is_sample_accurate() {
+        let sample_rate = 44100;
+        let wave = AutomatedWave::new(Wave::from(SineWave::with_frequency(440.0)))
+            .with_frequency(Automation::new(1.0).then(18.0, 2.0, CurveShape::Exponential));
+        let Channel::Mono(samples) = wave.render(sample_rate, 18.0) else {
+            panic!("Expected a mono render");
+        };
+        // An octave glide over 18 seconds: the phase is the integral of 440 * 2^(x / 18)
+        let count = samples.sample_count().unwrap();
+        for i in count - 1000..count {
+            let x = i as f64 / sample_rate as f64;
+            let phase = TAU * 440.0 * 18.0 / LN_2 * (2f64.powf(x / 18.0) - 1.0);
+            assert!((samples.sample_f32(i).unwrap() as f64 - phase.sin()).abs() < 0.05);
+        }
+    }
+}
@@ -0,0 +1,309 @@
+use crate::{Note, SampleType, Samples};
+
+/// CPU clock of an NTSC NES in Hz. The APU timers count in these cycles
+pub const NES_CPU_CLOCK: f64 = 1_789_773.0;
+
+/// Clock of the Game Boy wave channel timer in Hz
+pub const GAME_BOY_WAVE_CLOCK: f64 = 2_097_152.0;
+
+/// Steps a sequencer every `period` cycles of `clock`, holding its output between steps
+/// exactly like the hardware does
+fn render_stepped<F>(
+    clock: f64,
+    period: f64,
+    sample_rate: u32,
+    seconds: f32,
+    mut step: F,
+) -> Samples
+where
+    F: FnMut() -> f32,
+{
+    let count = (sample_rate as f32 * seconds) as usize;
+    let cycles_per_sample = clock / sample_rate as f64;
+    let mut counter = 0.0;
+    let mut value = step();
+    let points = (0..count)
+        .map(|_| {
+            let out = value;
+            counter += cycles_per_sample;
+            while counter >= period {
+                counter -= period;
+                value = step();
+            }
+            out
+        })
+        .collect();
+    Samples::new(SampleType::Pointsf32(points)).with_sample_rate(sample_rate)
+}
+
+fn silence(sample_rate: u32, seconds: f32) -> Samples {
+    let count = (sample_rate as f32 * seconds) as usize;
+    Samples::new(SampleType::Pointsf32(vec![0.0; count])).with_sample_rate(sample_rate)
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Duty {
+    Eighth,
+    Quarter,
+    Half,
+    ThreeQuarters,
+}
+
+impl Duty {
+    pub const fn sequence(&self) -> [u8; 8] {
+        match self {
+            Duty::Eighth => [0, 1, 0, 0, 0, 0, 0, 0],
+            Duty::Quarter => [0, 1, 1, 0, 0, 0, 0, 0],
+            Duty::Half => [0, 1, 1, 1, 1, 0, 0, 0],
+            Duty::ThreeQuarters => [1, 0, 0, 1, 1, 1, 1, 1],
+        }
+    }
+}
+
+/// NES APU pulse channel
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct PulseChannel {
+    period: u16,
+    pub duty: Duty,
+    /// From 0 to 15
+    pub volume: u8,
+}
+
+impl PulseChannel {
+    /// Tunes the 11 bit timer as close to `note` as it goes
+    pub fn new(note: Note) -> PulseChannel {
+        let period = NES_CPU_CLOCK / (16.0 * note.frequency() as f64) - 1.0;
+        PulseChannel::with_period(period.round().clamp(0.0, 0x7FF as f64) as u16)
+    }
+
+    pub fn with_period(period: u16) -> PulseChannel {
+        PulseChannel {
+            period: period & 0x7FF,
+            duty: Duty::Half,
+            volume: 15,
+        }
+    }
+
+    pub fn with_duty(mut self, duty: Duty) -> PulseChannel {
+        self.duty = duty;
+        self
+    }
+
+    pub fn with_volume(mut self, volume: u8) -> PulseChannel {
+        self.volume = volume.min(15);
+        self
+    }
+
+    pub fn period(&self) -> u16 {
+        self.period
+    }
+
+    /// Frequency actually played, after quantizing to the timer
+    pub fn frequency(&self) -> f32 {
+        (NES_CPU_CLOCK / (16.0 * (self.period as f64 + 1.0))) as f32
+    }
+
+    pub fn render(&self, sample_rate: u32, seconds: f32) -> Samples {
+        // The hardware mutes periods below 8 instead of playing them ultrasonic
+        if self.period < 8 {
+            return silence(sample_rate, seconds);
+        }
+        let sequence = self.duty.sequence();
+        let level = self.volume as f32 / 15.0;
+        let mut position = 0;
+        // The timer is clocked every other CPU cycle
+        let period = 2.0 * (self.period as f64 + 1.0);
+        render_stepped(NES_CPU_CLOCK, period, sample_rate, seconds, || {
+            position = (position + 1) % 8;
+            if sequence[position] == 1 {
+                level
+            } else {
+                -level
+            }
+        })
+    }
+}
+
+/// NES APU triangle channel: a 32 step, 4 bit staircase with no volume control
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct TriangleChannel {
+    period: u16,
+}
+
+impl TriangleChannel {
+    pub fn new(note: Note) -> TriangleChannel {
+        let period = NES_CPU_CLOCK / (32.0 * note.frequency() as f64) - 1.0;
+        TriangleChannel::with_period(period.round().clamp(0.0, 0x7FF as f64) as u16)
+    }
+
+    pub fn with_period(period: u16) -> TriangleChannel {
+        TriangleChannel {
+            period: period & 0x7FF,
+        }
+    }
+
+    pub fn period(&self) -> u16 {
+        self.period
+    }
+
+    pub fn frequency(&self) -> f32 {
+        (NES_CPU_CLOCK / (32.0 * (self.period as f64 + 1.0))) as f32
+    }
+
+    pub fn render(&self, sample_rate: u32, seconds: f32) -> Samples {
+        let mut position = 0u8;
+        let period = self.period as f64 + 1.0;
+        render_stepped(NES_CPU_CLOCK, period, sample_rate, seconds, || {
+            position = (position + 1) % 32;
+            let level = if position < 16 {
+                15 - position
+            } else {
+                position - 16
+            };
+            level as f32 / 7.5 - 1.0
+        })
+    }
+}
+
+/// NES APU noise channel: a 15 bit linear feedback shift register
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct NoiseChannel {
+    /// Index into [`NoiseChannel::PERIODS`]
+    pub period_index: u8,
+    /// Short mode taps bit 6 instead of bit 1, giving a metallic 93 step loop
+    pub short: bool,
+    pub volume: u8,
+}
+
+impl NoiseChannel {
+    /// Timer periods in CPU cycles (NTSC)
+    pub const PERIODS: [u16; 16] = [
+        4, 8, 16, 32, 64, 96, 128, 160, 202, 254, 380, 508, 762, 1016, 2034, 4068,
+    ];
+
+    pub fn new(period_index: u8) -> NoiseChannel {
+        NoiseChannel {
+            period_index: period_index.min(15),
+            short: false,
+            volume: 15,
+        }
+    }
+
+    /// Short mode noise with the period whose 93 step loop is closest in pitch to `note`
+    pub fn from_note(note: Note) -> NoiseChannel {
+        let target = NES_CPU_CLOCK / (93.0 * note.frequency() as f64);
+        let index = (0..16)
+            .min_by(|&a, &b| {
+                let distance = |i: usize| (Self::PERIODS[i] as f64 / target).ln().abs();
+                distance(a).total_cmp(&distance(b))
+            })
+            .unwrap_or_default();
+        NoiseChannel::new(index as u8).with_short(true)
+    }
+
+    pub fn with_short(mut self, short: bool) -> NoiseChannel {
+        self.short = short;
+        self
+    }
+
+    pub fn with_volume(mut self, volume: u8) -> NoiseChannel {
+        self.volume = volume.min(15);
+        self
+    }
+
+    pub fn render(&self, sample_rate: u32, seconds: f32) -> Samples {
+        let level = self.volume as f32 / 15.0;
+        let tap = if self.short { 6 } else { 1 };
+        let mut register = 1u16;
+        let period = Self::PERIODS[self.period_index.min(15) as usize] as f64;
+        render_stepped(NES_CPU_CLOCK, period, sample_rate, seconds, || {
+            let feedback = (register ^ (register >> tap)) & 1;
+            register = (register >> 1) | (feedback << 14);
+            // The channel is silenced while bit 0 is set
+            if register & 1 == 0 {
+                level
+            } else {
+                -level
+            }
+        })
+    }
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum WaveVolume {
+    Mute,
+    Full,
+    Half,
+    Quarter,
+}
+
+impl WaveVolume {
+    const fn shift(&self) -> u8 {
+        match self {
+            WaveVolume::Mute | WaveVolume::Full => 0,
+            WaveVolume::Half => 1,
+            WaveVolume::Quarter => 2,
+        }
+    }
+}
+
+/// Game Boy wave channel: 32 user defined 4 bit samples
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct WaveChannel {
+    /// Samples from 0 to 15
+    pub table: [u8; 32],
+    frequency: u16,
+    pub volume: WaveVolume,
+}
+
+impl WaveChannel {
+    pub fn new(table: [u8; 32], note: Note) -> WaveChannel {
+        // Frequency is 65536 / (2048 - x) Hz
+        let x = 2048.0 - 65536.0 / note.frequency() as f64;
+        WaveChannel::with_frequency_register(table, x.round().clamp(0.0, 2047.0) as u16)
+    }
+
+    pub fn with_frequency_register(table: [u8; 32], frequency: u16) -> WaveChannel {
+        WaveChannel {
+            table: table.map(|x| x & 0xF),
+            frequency: frequency & 0x7FF,
+            volume: WaveVolume::Full,
+        }
+    }
+
+    pub fn with_volume(mut self, volume: WaveVolume) -> WaveChannel {
+        self.volume = volume;
+        self
+    }
+
+    /// Builds a table from a function of the phase (from 0 to 1) returning values from -1 to 1
+    pub fn table_from<F: Fn(f32) -> f32>(f: F) -> [u8; 32] {
+        let mut table = [0; 32];
+        for (i, x) in table.iter_mut().enumerate() {
+            *x = ((f(i as f32 / 32.0).clamp(-1.0, 1.0) + 1.0) * 7.5).round() as u8;
+        }
+        table
+    }
+
+    pub fn frequency_register(&self) -> u16 {
+        self.frequency
+    }
+
+    pub fn frequency(&self) -> f32 {
+        (65536.0 / (2048.0 - self.frequency as f64)) as f32
+    }
+
+    pub fn render(&self, sample_rate: u32, seconds: f32) -> Samples {
+        if self.volume == WaveVolume::Mute {
+            return silence(sample_rate, seconds);
+        }
+        let shift = self.volume.shift();
+        let center = 7.5 / (1 << shift) as f32;
+        let mut position = 0;
+        let period = 2048.0 - self.frequency as f64;
+        render_stepped(GAME_BOY_WAVE_CLOCK, period, sample_rate, seconds, || {
+            position = (position + 1) % 32;
+            ((self.table[position] >> shift) as f32 - center) / 7.5
+        })
+    }
+}
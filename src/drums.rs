@@ -0,0 +1,365 @@
+use crate::{
+    envelope::Envelope,
+    filter::{Filter, FilterType},
+    noise::Noise,
+    sweep::Sweep,
+    voice::{Instrument, OneShot},
+    Bb2, Eb2, Gb2, Note, SampleType, Samples, B2, C2, D2, D3, G2,
+};
+
+fn samples(points: Vec<f32>, sample_rate: u32) -> Samples {
+    Samples::new(SampleType::Pointsf32(points)).with_sample_rate(sample_rate)
+}
+
+/// Sine sweeping down from `start` to `end`, with a short noise click on top
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Kick {
+    pub start: f32,
+    pub end: f32,
+    pub pitch_decay: f32,
+    pub decay: f32,
+    pub click: f32,
+    pub amplitude: f32,
+    pub seed: u64,
+}
+
+impl Default for Kick {
+    fn default() -> Kick {
+        Kick {
+            start: 150.0,
+            end: 45.0,
+            pitch_decay: 0.06,
+            decay: 0.5,
+            click: 0.3,
+            amplitude: 1.0,
+            seed: 0,
+        }
+    }
+}
+
+impl Kick {
+    pub fn render(&self, sample_rate: u32) -> Samples {
+        let rate = sample_rate as f32;
+        let sweep = Sweep::exponential(self.start, self.end, self.pitch_decay);
+        let body = Envelope::percussive(0.002, self.decay);
+        let click = Envelope::percussive(0.0005, 0.01);
+        let mut noise = Noise::white().with_seed(self.seed).generator();
+        let mut highpass = Filter::new(FilterType::HighPass, 2000.0, 0.7, sample_rate);
+
+        let points = (0..(rate * (self.decay + 0.002)) as usize)
+            .map(|i| {
+                let x = i as f32 / rate;
+                let tone = sweep.at(x) * body.at(x, None);
+                let click =
+                    self.click * click.at(x, None) * highpass.process(noise.next().unwrap());
+                self.amplitude * (tone + click).clamp(-1.0, 1.0)
+            })
+            .collect();
+        samples(points, sample_rate)
+    }
+}
+
+/// Two tuned body partials mixed with band passed noise for the snares
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Snare {
+    pub tone: f32,
+    pub tone_decay: f32,
+    pub noise_decay: f32,
+    /// Mix between body (0) and snares (1)
+    pub snappy: f32,
+    pub amplitude: f32,
+    pub seed: u64,
+}
+
+impl Default for Snare {
+    fn default() -> Snare {
+        Snare {
+            tone: 185.0,
+            tone_decay: 0.12,
+            noise_decay: 0.22,
+            snappy: 0.6,
+            amplitude: 1.0,
+            seed: 0,
+        }
+    }
+}
+
+impl Snare {
+    pub fn render(&self, sample_rate: u32) -> Samples {
+        let rate = sample_rate as f32;
+        let low = Sweep::exponential(self.tone * 1.4, self.tone, 0.02);
+        let high = Sweep::exponential(self.tone * 2.6, self.tone * 1.8, 0.02);
+        let tone = Envelope::percussive(0.001, self.tone_decay);
+        let snares = Envelope::percussive(0.001, self.noise_decay);
+        let mut noise = Noise::white().with_seed(self.seed).generator();
+        let mut filter = Filter::new(FilterType::HighPass, 1800.0, 0.7, sample_rate)
+            .then(Filter::new(FilterType::LowPass, 9000.0, 0.7, sample_rate));
+
+        let length = self.tone_decay.max(self.noise_decay) + 0.001;
+        let points = (0..(rate * length) as usize)
+            .map(|i| {
+                let x = i as f32 / rate;
+                let body = (low.at(x) + 0.5 * high.at(x)) / 1.5 * tone.at(x, None);
+                let snares = filter.process(noise.next().unwrap()) * snares.at(x, None);
+                self.amplitude * ((1.0 - self.snappy) * body + self.snappy * snares)
+            })
+            .collect();
+        samples(points, sample_rate)
+    }
+}
+
+/// Metallic cluster of square waves and noise, high passed
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct HiHat {
+    pub open: bool,
+    pub decay: f32,
+    pub amplitude: f32,
+    pub seed: u64,
+}
+
+impl HiHat {
+    /// Frequencies of the six square oscillators of the TR-808 cymbal circuit
+    const METAL: [f32; 6] = [205.3, 304.4, 369.6, 522.7, 540.0, 800.0];
+
+    pub fn closed() -> HiHat {
+        HiHat {
+            open: false,
+            decay: 0.05,
+            amplitude: 1.0,
+            seed: 0,
+        }
+    }
+
+    pub fn open() -> HiHat {
+        HiHat {
+            open: true,
+            decay: 0.4,
+            ..HiHat::closed()
+        }
+    }
+
+    pub fn render(&self, sample_rate: u32) -> Samples {
+        let rate = sample_rate as f32;
+        let envelope = Envelope::percussive(0.001, self.decay);
+        let mut noise = Noise::white().with_seed(self.seed).generator();
+        let mut filter = Filter::butterworth(FilterType::HighPass, 4, 7000.0, sample_rate).then(
+            Filter::new(FilterType::Peak(6.0), 10000.0, 1.0, sample_rate),
+        );
+
+        let points = (0..(rate * (self.decay + 0.001)) as usize)
+            .map(|i| {
+                let x = i as f32 / rate;
+                let metal = Self::METAL
+                    .iter()
+                    .map(|f| if (f * x).fract() < 0.5 { 1.0 } else { -1.0 })
+                    .sum::<f32>()
+                    / Self::METAL.len() as f32;
+                let raw = 0.35 * (metal + noise.next().unwrap());
+                self.amplitude * (filter.process(raw) * envelope.at(x, None)).clamp(-1.0, 1.0)
+            })
+            .collect();
+        samples(points, sample_rate)
+    }
+}
+
+/// Pitched drum that bends down onto its note
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Tom {
+    frequency: f32,
+    pub bend: f32,
+    pub decay: f32,
+    pub amplitude: f32,
+    pub seed: u64,
+}
+
+impl Tom {
+    pub fn new(note: Note) -> Tom {
+        Tom::with_frequency(note.frequency())
+    }
+
+    pub fn with_frequency(frequency: f32) -> Tom {
+        Tom {
+            frequency,
+            bend: 1.5,
+            decay: 0.4,
+            amplitude: 1.0,
+            seed: 0,
+        }
+    }
+
+    pub fn low() -> Tom {
+        Tom::with_frequency(90.0)
+    }
+
+    pub fn mid() -> Tom {
+        Tom::with_frequency(130.0)
+    }
+
+    pub fn high() -> Tom {
+        Tom::with_frequency(180.0)
+    }
+
+    pub fn frequency(&self) -> f32 {
+        self.frequency
+    }
+
+    pub fn render(&self, sample_rate: u32) -> Samples {
+        let rate = sample_rate as f32;
+        let sweep = Sweep::exponential(self.frequency * self.bend, self.frequency, 0.08);
+        let body = Envelope::percussive(0.002, self.decay);
+        let attack = Envelope::percussive(0.001, 0.03);
+        let mut noise = Noise::white().with_seed(self.seed).generator();
+        let mut lowpass = Filter::new(FilterType::LowPass, 3000.0, 0.7, sample_rate);
+
+        let points = (0..(rate * (self.decay + 0.002)) as usize)
+            .map(|i| {
+                let x = i as f32 / rate;
+                let skin = 0.2 * lowpass.process(noise.next().unwrap()) * attack.at(x, None);
+                self.amplitude * (0.8 * sweep.at(x) * body.at(x, None) + skin)
+            })
+            .collect();
+        samples(points, sample_rate)
+    }
+}
+
+/// A few quick band passed noise bursts followed by a longer tail, like several hands clapping
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Clap {
+    pub bursts: u32,
+    pub spacing: f32,
+    pub decay: f32,
+    pub amplitude: f32,
+    pub seed: u64,
+}
+
+impl Default for Clap {
+    fn default() -> Clap {
+        Clap {
+            bursts: 3,
+            spacing: 0.01,
+            decay: 0.25,
+            amplitude: 1.0,
+            seed: 0,
+        }
+    }
+}
+
+impl Clap {
+    pub fn render(&self, sample_rate: u32) -> Samples {
+        let rate = sample_rate as f32;
+        let burst = Envelope::percussive(0.0005, self.spacing);
+        let tail = Envelope::percussive(0.001, self.decay);
+        let mut noise = Noise::white().with_seed(self.seed).generator();
+        let mut bandpass = Filter::new(FilterType::BandPass, 1200.0, 1.2, sample_rate);
+        let tail_start = self.bursts as f32 * self.spacing;
+
+        let points = (0..(rate * (tail_start + self.decay + 0.001)) as usize)
+            .map(|i| {
+                let x = i as f32 / rate;
+                let level = if x < tail_start {
+                    burst.at(x % self.spacing, None)
+                } else {
+                    tail.at(x - tail_start, None)
+                };
+                self.amplitude
+                    * (2.0 * bandpass.process(noise.next().unwrap()) * level).clamp(-1.0, 1.0)
+            })
+            .collect();
+        samples(points, sample_rate)
+    }
+}
+
+/// Plays the drums above from notes, laid out like the General MIDI percussion map
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct DrumKit {
+    pub kick: Kick,
+    pub snare: Snare,
+    pub closed_hat: HiHat,
+    pub open_hat: HiHat,
+    pub low_tom: Tom,
+    pub mid_tom: Tom,
+    pub high_tom: Tom,
+    pub clap: Clap,
+}
+
+impl Default for DrumKit {
+    fn default() -> DrumKit {
+        DrumKit {
+            kick: Kick::default(),
+            snare: Snare::default(),
+            closed_hat: HiHat::closed(),
+            open_hat: HiHat::open(),
+            low_tom: Tom::low(),
+            mid_tom: Tom::mid(),
+            high_tom: Tom::high(),
+            clap: Clap::default(),
+        }
+    }
+}
+
+impl DrumKit {
+    pub const KICK: Note = C2;
+    pub const SNARE: Note = D2;
+    pub const CLAP: Note = Eb2;
+    pub const CLOSED_HAT: Note = Gb2;
+    pub const LOW_TOM: Note = G2;
+    pub const OPEN_HAT: Note = Bb2;
+    pub const MID_TOM: Note = B2;
+    pub const HIGH_TOM: Note = D3;
+
+    /// Renders the drum mapped to `note`, or `None` if nothing is
+    pub fn render(&self, note: Note, velocity: f32, sample_rate: u32) -> Option<Samples> {
+        let samples = match note {
+            Self::KICK => Kick {
+                amplitude: self.kick.amplitude * velocity,
+                ..self.kick
+            }
+            .render(sample_rate),
+            Self::SNARE => Snare {
+                amplitude: self.snare.amplitude * velocity,
+                ..self.snare
+            }
+            .render(sample_rate),
+            Self::CLAP => Clap {
+                amplitude: self.clap.amplitude * velocity,
+                ..self.clap
+            }
+            .render(sample_rate),
+            Self::CLOSED_HAT | Self::OPEN_HAT => {
+                let hat = if note == Self::OPEN_HAT {
+                    self.open_hat
+                } else {
+                    self.closed_hat
+                };
+                HiHat {
+                    amplitude: hat.amplitude * velocity,
+                    ..hat
+                }
+                .render(sample_rate)
+            }
+            Self::LOW_TOM | Self::MID_TOM | Self::HIGH_TOM => {
+                let mut tom = match note {
+                    Self::LOW_TOM => self.low_tom,
+                    Self::MID_TOM => self.mid_tom,
+                    _ => self.high_tom,
+                };
+                tom.amplitude *= velocity;
+                tom.render(sample_rate)
+            }
+            _ => return None,
+        };
+        Some(samples)
+    }
+}
+
+impl Instrument for DrumKit {
+    type Voice = OneShot;
+
+    /// Drums ring out fully, whatever the note length. Unmapped notes are silent
+    fn voice(&self, note: Note, velocity: f32, sample_rate: u32) -> OneShot {
+        let samples = self
+            .render(note, velocity, sample_rate)
+            .unwrap_or_else(|| samples(Vec::new(), sample_rate));
+        OneShot::new(&samples).ignoring_release()
+    }
+}
@@ -0,0 +1,64 @@
+/// Attack, decay, sustain and release envelope. Times are in seconds, sustain is a level from 0 to 1.
+/// Decay and release follow a cubic curve, which sounds close to an analog exponential but still
+/// reaches its target in the given time
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Envelope {
+    pub attack: f32,
+    pub decay: f32,
+    pub sustain: f32,
+    pub release: f32,
+}
+
+impl Envelope {
+    pub fn new(attack: f32, decay: f32, sustain: f32, release: f32) -> Envelope {
+        Envelope {
+            attack,
+            decay,
+            sustain: sustain.clamp(0.0, 1.0),
+            release,
+        }
+    }
+
+    /// An envelope that dies away on its own, without needing a release
+    pub fn percussive(attack: f32, decay: f32) -> Envelope {
+        Envelope::new(attack, decay, 0.0, 0.0)
+    }
+
+    /// Level `x` seconds after the note started, if it was held until `released` seconds
+    /// (`None` if it is still held)
+    pub fn at(&self, x: f32, released: Option<f32>) -> f32 {
+        match released {
+            Some(released) if x >= released => {
+                let progress = (x - released) / self.release;
+                if progress >= 1.0 || progress.is_nan() {
+                    0.0
+                } else {
+                    self.held(released) * (1.0 - progress).powi(3)
+                }
+            }
+            _ => self.held(x),
+        }
+    }
+
+    /// Time at which the envelope falls silent if the note is released after `released` seconds
+    pub fn length(&self, released: f32) -> f32 {
+        if self.sustain == 0.0 {
+            (self.attack + self.decay).min(released + self.release)
+        } else {
+            released + self.release
+        }
+    }
+
+    fn held(&self, x: f32) -> f32 {
+        if x < 0.0 {
+            0.0
+        } else if x < self.attack {
+            x / self.attack
+        } else if x < self.attack + self.decay {
+            let progress = (x - self.attack) / self.decay;
+            self.sustain + (1.0 - self.sustain) * (1.0 - progress).powi(3)
+        } else {
+            self.sustain
+        }
+    }
+}
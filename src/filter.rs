@@ -0,0 +1,293 @@
+use std::f32::consts::{FRAC_1_SQRT_2, PI, TAU};
+
+use crate::{SampleType, Samples, Wave};
+
+/// Filter responses from the RBJ audio EQ cookbook. Gains are in dB
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum FilterType {
+    LowPass,
+    HighPass,
+    BandPass,
+    Notch,
+    AllPass,
+    Peak(f32),
+    LowShelf(f32),
+    HighShelf(f32),
+    FirstOrderLowPass,
+    FirstOrderHighPass,
+}
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Biquad {
+    kind: FilterType,
+    cutoff: f32,
+    q: f32,
+    sample_rate: u32,
+    b: [f32; 3],
+    a: [f32; 2],
+    x: [f32; 2],
+    y: [f32; 2],
+}
+
+impl Biquad {
+    pub fn new(kind: FilterType, cutoff: f32, q: f32, sample_rate: u32) -> Biquad {
+        let mut biquad = Biquad {
+            kind,
+            cutoff,
+            q,
+            sample_rate,
+            b: [1.0, 0.0, 0.0],
+            a: [0.0, 0.0],
+            x: [0.0; 2],
+            y: [0.0; 2],
+        };
+        biquad.update();
+        biquad
+    }
+
+    pub fn kind(&self) -> FilterType {
+        self.kind
+    }
+
+    pub fn cutoff(&self) -> f32 {
+        self.cutoff
+    }
+
+    pub fn q(&self) -> f32 {
+        self.q
+    }
+
+    /// Changes the cutoff without clearing the filter state, so it can be swept while running
+    pub fn set_cutoff(&mut self, cutoff: f32) {
+        self.cutoff = cutoff;
+        self.update();
+    }
+
+    pub fn set_q(&mut self, q: f32) {
+        self.q = q;
+        self.update();
+    }
+
+    pub fn reset(&mut self) {
+        self.x = [0.0; 2];
+        self.y = [0.0; 2];
+    }
+
+    pub fn process(&mut self, x: f32) -> f32 {
+        let [b0, b1, b2] = self.b;
+        let [a1, a2] = self.a;
+        let y = b0 * x + b1 * self.x[0] + b2 * self.x[1] - a1 * self.y[0] - a2 * self.y[1];
+        self.x = [x, self.x[0]];
+        self.y = [y, self.y[0]];
+        y
+    }
+
+    fn update(&mut self) {
+        let nyquist = self.sample_rate as f32 / 2.0;
+        let w0 = TAU * self.cutoff.clamp(1.0, nyquist * 0.999) / self.sample_rate as f32;
+        let (sin, cos) = w0.sin_cos();
+        let alpha = sin / (2.0 * self.q);
+
+        let (b, a) = match self.kind {
+            FilterType::LowPass => (
+                [(1.0 - cos) / 2.0, 1.0 - cos, (1.0 - cos) / 2.0],
+                [1.0 + alpha, -2.0 * cos, 1.0 - alpha],
+            ),
+            FilterType::HighPass => (
+                [(1.0 + cos) / 2.0, -(1.0 + cos), (1.0 + cos) / 2.0],
+                [1.0 + alpha, -2.0 * cos, 1.0 - alpha],
+            ),
+            FilterType::BandPass => ([alpha, 0.0, -alpha], [1.0 + alpha, -2.0 * cos, 1.0 - alpha]),
+            FilterType::Notch => (
+                [1.0, -2.0 * cos, 1.0],
+                [1.0 + alpha, -2.0 * cos, 1.0 - alpha],
+            ),
+            FilterType::AllPass => (
+                [1.0 - alpha, -2.0 * cos, 1.0 + alpha],
+                [1.0 + alpha, -2.0 * cos, 1.0 - alpha],
+            ),
+            FilterType::Peak(gain) => {
+                let a = 10f32.powf(gain / 40.0);
+                (
+                    [1.0 + alpha * a, -2.0 * cos, 1.0 - alpha * a],
+                    [1.0 + alpha / a, -2.0 * cos, 1.0 - alpha / a],
+                )
+            }
+            FilterType::LowShelf(gain) => {
+                let a = 10f32.powf(gain / 40.0);
+                let k = 2.0 * a.sqrt() * alpha;
+                (
+                    [
+                        a * ((a + 1.0) - (a - 1.0) * cos + k),
+                        2.0 * a * ((a - 1.0) - (a + 1.0) * cos),
+                        a * ((a + 1.0) - (a - 1.0) * cos - k),
+                    ],
+                    [
+                        (a + 1.0) + (a - 1.0) * cos + k,
+                        -2.0 * ((a - 1.0) + (a + 1.0) * cos),
+                        (a + 1.0) + (a - 1.0) * cos - k,
+                    ],
+                )
+            }
+            FilterType::HighShelf(gain) => {
+                let a = 10f32.powf(gain / 40.0);
+                let k = 2.0 * a.sqrt() * alpha;
+                (
+                    [
+                        a * ((a + 1.0) + (a - 1.0) * cos + k),
+                        -2.0 * a * ((a - 1.0) + (a + 1.0) * cos),
+                        a * ((a + 1.0) + (a - 1.0) * cos - k),
+                    ],
+                    [
+                        (a + 1.0) - (a - 1.0) * cos + k,
+                        2.0 * ((a - 1.0) - (a + 1.0) * cos),
+                        (a + 1.0) - (a - 1.0) * cos - k,
+                    ],
+                )
+            }
+            FilterType::FirstOrderLowPass => {
+                let k = (w0 / 2.0).tan();
+                ([k, k, 0.0], [k + 1.0, k - 1.0, 0.0])
+            }
+            FilterType::FirstOrderHighPass => {
+                let k = (w0 / 2.0).tan();
+                ([1.0, -1.0, 0.0], [k + 1.0, k - 1.0, 0.0])
+            }
+        };
+
+        let a0 = a[0];
+        self.b = [b[0] / a0, b[1] / a0, b[2] / a0];
+        self.a = [a[1] / a0, a[2] / a0];
+    }
+}
+
+/// A cascade of biquads, processed one after another
+#[derive(Clone, Debug, PartialEq)]
+pub struct Filter {
+    stages: Vec<Biquad>,
+    sample_rate: u32,
+}
+
+impl Filter {
+    pub fn new(kind: FilterType, cutoff: f32, q: f32, sample_rate: u32) -> Filter {
+        Filter {
+            stages: vec![Biquad::new(kind, cutoff, q, sample_rate)],
+            sample_rate,
+        }
+    }
+
+    pub fn from_stages(stages: Vec<Biquad>, sample_rate: u32) -> Filter {
+        assert!(stages.iter().all(|s| s.sample_rate == sample_rate));
+        Filter {
+            stages,
+            sample_rate,
+        }
+    }
+
+    /// Butterworth low or high pass of any order
+    /// # Panics
+    /// Panics if `kind` is not `LowPass` or `HighPass`, or `order` is 0
+    #[track_caller]
+    pub fn butterworth(kind: FilterType, order: u32, cutoff: f32, sample_rate: u32) -> Filter {
+        assert!(order > 0, "Filter order must be at least 1");
+        let first_order = match kind {
+            FilterType::LowPass => FilterType::FirstOrderLowPass,
+            FilterType::HighPass => FilterType::FirstOrderHighPass,
+            _ => panic!("Butterworth filters are only low or high pass"),
+        };
+
+        let mut stages: Vec<Biquad> = (0..order / 2)
+            .map(|k| {
+                let q = 1.0 / (2.0 * ((2 * k + 1) as f32 * PI / (2 * order) as f32).sin());
+                Biquad::new(kind, cutoff, q, sample_rate)
+            })
+            .collect();
+        if order % 2 == 1 {
+            stages.push(Biquad::new(first_order, cutoff, FRAC_1_SQRT_2, sample_rate));
+        }
+        Filter::from_stages(stages, sample_rate)
+    }
+
+    /// Linkwitz-Riley crossover filter, two cascaded Butterworth filters of half the order
+    /// # Panics
+    /// Panics if `order` is odd or 0, or `kind` is not `LowPass` or `HighPass`
+    #[track_caller]
+    pub fn linkwitz_riley(kind: FilterType, order: u32, cutoff: f32, sample_rate: u32) -> Filter {
+        assert!(
+            order > 0 && order.is_multiple_of(2),
+            "Linkwitz-Riley order must be even"
+        );
+        let half = Filter::butterworth(kind, order / 2, cutoff, sample_rate);
+        Filter::from_stages(half.stages.repeat(2), sample_rate)
+    }
+
+    pub fn then(mut self, other: Filter) -> Filter {
+        assert_eq!(self.sample_rate, other.sample_rate);
+        self.stages.extend(other.stages);
+        self
+    }
+
+    pub fn stages(&self) -> &[Biquad] {
+        &self.stages
+    }
+
+    pub fn sample_rate(&self) -> u32 {
+        self.sample_rate
+    }
+
+    pub fn set_cutoff(&mut self, cutoff: f32) {
+        for stage in &mut self.stages {
+            stage.set_cutoff(cutoff);
+        }
+    }
+
+    pub fn reset(&mut self) {
+        for stage in &mut self.stages {
+            stage.reset();
+        }
+    }
+
+    pub fn process(&mut self, x: f32) -> f32 {
+        self.stages.iter_mut().fold(x, |x, stage| stage.process(x))
+    }
+
+    /// Filters the first `seconds` of `samples`
+    pub fn process_samples(&mut self, samples: &Samples, seconds: f32) -> Samples {
+        self.process_samples_with(samples, seconds, |_| None)
+    }
+
+    /// Filters the first `seconds` of `samples` while `cutoff` is asked for a new cutoff
+    /// (in Hz) at every sample, given the time in seconds
+    pub fn process_samples_with<F>(
+        &mut self,
+        samples: &Samples,
+        seconds: f32,
+        mut cutoff: F,
+    ) -> Samples
+    where
+        F: FnMut(f32) -> Option<f32>,
+    {
+        assert_eq!(samples.sample_rate(), self.sample_rate);
+        let sample_rate = self.sample_rate as f32;
+        let mut count = (sample_rate * seconds) as usize;
+        if let Some(len) = samples.sample_count() {
+            count = count.min(len);
+        }
+
+        let points = (0..count)
+            .map(|i| {
+                if let Some(cutoff) = cutoff(i as f32 / sample_rate) {
+                    self.set_cutoff(cutoff);
+                }
+                self.process(samples.sample_f32(i).unwrap())
+            })
+            .collect();
+        Samples::new(SampleType::Pointsf32(points)).with_sample_rate(self.sample_rate)
+    }
+
+    pub fn process_wave(&mut self, wave: &Wave, seconds: f32) -> Samples {
+        let samples =
+            Samples::new(SampleType::Wave(wave.clone())).with_sample_rate(self.sample_rate);
+        self.process_samples(&samples, seconds)
+    }
+}
@@ -0,0 +1,280 @@
+use std::f32::consts::{PI, TAU};
+
+use crate::{
+    filter::{Biquad, FilterType},
+    Lfo, Note, SampleType, Samples,
+};
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Formant {
+    pub frequency: f32,
+    pub bandwidth: f32,
+    /// Level relative to the first formant, in dB
+    pub gain: f32,
+}
+
+impl Formant {
+    pub const fn new(frequency: f32, bandwidth: f32, gain: f32) -> Formant {
+        Formant {
+            frequency,
+            bandwidth,
+            gain,
+        }
+    }
+
+    pub fn lerp(&self, other: &Formant, t: f32) -> Formant {
+        Formant {
+            frequency: self.frequency + (other.frequency - self.frequency) * t,
+            bandwidth: self.bandwidth + (other.bandwidth - self.bandwidth) * t,
+            gain: self.gain + (other.gain - self.gain) * t,
+        }
+    }
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Vowel {
+    A,
+    E,
+    I,
+    O,
+    U,
+}
+
+impl Vowel {
+    /// First five formants of a tenor voice
+    pub const fn formants(&self) -> [Formant; 5] {
+        match self {
+            Vowel::A => [
+                Formant::new(650.0, 80.0, 0.0),
+                Formant::new(1080.0, 90.0, -6.0),
+                Formant::new(2650.0, 120.0, -7.0),
+                Formant::new(2900.0, 130.0, -8.0),
+                Formant::new(3250.0, 140.0, -22.0),
+            ],
+            Vowel::E => [
+                Formant::new(400.0, 70.0, 0.0),
+                Formant::new(1700.0, 80.0, -14.0),
+                Formant::new(2600.0, 100.0, -12.0),
+                Formant::new(3200.0, 120.0, -14.0),
+                Formant::new(3580.0, 120.0, -20.0),
+            ],
+            Vowel::I => [
+                Formant::new(290.0, 40.0, 0.0),
+                Formant::new(1870.0, 90.0, -15.0),
+                Formant::new(2800.0, 100.0, -18.0),
+                Formant::new(3250.0, 120.0, -20.0),
+                Formant::new(3540.0, 120.0, -30.0),
+            ],
+            Vowel::O => [
+                Formant::new(400.0, 40.0, 0.0),
+                Formant::new(800.0, 80.0, -10.0),
+                Formant::new(2600.0, 100.0, -12.0),
+                Formant::new(2800.0, 120.0, -12.0),
+                Formant::new(3000.0, 120.0, -26.0),
+            ],
+            Vowel::U => [
+                Formant::new(350.0, 40.0, 0.0),
+                Formant::new(600.0, 60.0, -20.0),
+                Formant::new(2700.0, 100.0, -17.0),
+                Formant::new(2900.0, 120.0, -14.0),
+                Formant::new(3300.0, 120.0, -26.0),
+            ],
+        }
+    }
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Topology {
+    /// Band pass filters side by side, each weighted by its formant gain
+    Parallel,
+    /// Resonators in series, so the formant levels follow from the frequencies alone
+    Cascade,
+}
+
+/// Two pole resonator with unity gain at DC, after Klatt
+#[derive(Copy, Clone, Debug, PartialEq)]
+struct Resonator {
+    a: f32,
+    b: f32,
+    c: f32,
+    y: [f32; 2],
+}
+
+impl Resonator {
+    fn new() -> Resonator {
+        Resonator {
+            a: 1.0,
+            b: 0.0,
+            c: 0.0,
+            y: [0.0; 2],
+        }
+    }
+
+    fn set(&mut self, formant: &Formant, sample_rate: u32) {
+        let t = 1.0 / sample_rate as f32;
+        let r = (-PI * formant.bandwidth * t).exp();
+        self.b = 2.0 * r * (TAU * formant.frequency * t).cos();
+        self.c = -r * r;
+        self.a = 1.0 - self.b - self.c;
+    }
+
+    fn process(&mut self, x: f32) -> f32 {
+        let y = self.a * x + self.b * self.y[0] + self.c * self.y[1];
+        self.y = [y, self.y[0]];
+        y
+    }
+}
+
+/// A glottal pulse train shaped by vowel formants
+#[derive(Clone, Debug, PartialEq)]
+pub struct FormantSynth {
+    frequency: f32,
+    pub amplitude: f32,
+    pub topology: Topology,
+    /// Depth in cents
+    pub vibrato: Option<Lfo>,
+    vowels: Vec<(f32, Vowel)>,
+}
+
+impl FormantSynth {
+    /// Samples between formant updates while morphing
+    const UPDATE: usize = 32;
+
+    pub fn new(note: Note, vowel: Vowel) -> FormantSynth {
+        FormantSynth::with_frequency(note.frequency(), vowel).with_amplitude(note.amplitude())
+    }
+
+    pub fn with_frequency(frequency: f32, vowel: Vowel) -> FormantSynth {
+        FormantSynth {
+            frequency,
+            amplitude: 1.0,
+            topology: Topology::Parallel,
+            vibrato: None,
+            vowels: vec![(0.0, vowel)],
+        }
+    }
+
+    pub fn with_amplitude(mut self, amplitude: f32) -> FormantSynth {
+        self.amplitude = amplitude;
+        self
+    }
+
+    pub fn with_topology(mut self, topology: Topology) -> FormantSynth {
+        self.topology = topology;
+        self
+    }
+
+    pub fn with_vibrato(mut self, lfo: Lfo) -> FormantSynth {
+        self.vibrato = Some(lfo);
+        self
+    }
+
+    /// Glides the formants from the previous vowel so that they reach `vowel` at `x` seconds
+    pub fn morph_to(mut self, vowel: Vowel, x: f32) -> FormantSynth {
+        self.vowels.push((x, vowel));
+        self.vowels.sort_by(|a, b| a.0.total_cmp(&b.0));
+        self
+    }
+
+    pub fn frequency(&self) -> f32 {
+        self.frequency
+    }
+
+    pub fn formants_at(&self, x: f32) -> [Formant; 5] {
+        let next = self.vowels.iter().position(|&(at, _)| at > x);
+        match next {
+            Some(0) => self.vowels[0].1.formants(),
+            Some(i) => {
+                let (start, from) = self.vowels[i - 1];
+                let (end, to) = self.vowels[i];
+                let t = (x - start) / (end - start);
+                let (from, to) = (from.formants(), to.formants());
+                let mut formants = from;
+                for (formant, (a, b)) in formants.iter_mut().zip(from.iter().zip(to.iter())) {
+                    *formant = a.lerp(b, t);
+                }
+                formants
+            }
+            None => self.vowels[self.vowels.len() - 1].1.formants(),
+        }
+    }
+
+    /// Renders the voice, normalized so that its loudest sample is `amplitude`
+    pub fn render(&self, sample_rate: u32, seconds: f32) -> Samples {
+        let rate = sample_rate as f32;
+        let count = (rate * seconds) as usize;
+        let mut bandpass: Vec<Biquad> = self.vowels[0]
+            .1
+            .formants()
+            .iter()
+            .map(|f| {
+                Biquad::new(
+                    FilterType::BandPass,
+                    f.frequency,
+                    f.frequency / f.bandwidth,
+                    sample_rate,
+                )
+            })
+            .collect();
+        let mut resonators = [Resonator::new(); 5];
+        let mut gains = [1.0; 5];
+
+        let mut phase = 0.0f32;
+        let mut previous = 0.0;
+        let mut points: Vec<f32> = (0..count)
+            .map(|i| {
+                let x = i as f32 / rate;
+                if i % Self::UPDATE == 0 {
+                    for (j, formant) in self.formants_at(x).iter().enumerate() {
+                        bandpass[j].set_cutoff(formant.frequency);
+                        bandpass[j].set_q(formant.frequency / formant.bandwidth);
+                        resonators[j].set(formant, sample_rate);
+                        gains[j] = 10f32.powf(formant.gain / 20.0);
+                    }
+                }
+
+                // Derivative of a Rosenberg pulse, which includes the radiation at the lips
+                let pulse = glottal_pulse(phase);
+                let source = pulse - previous;
+                previous = pulse;
+                let frequency = match self.vibrato {
+                    Some(lfo) => self.frequency * 2f32.powf(lfo.at(x) / 1200.0),
+                    None => self.frequency,
+                };
+                phase = (phase + frequency / rate).fract();
+
+                match self.topology {
+                    Topology::Parallel => bandpass
+                        .iter_mut()
+                        .zip(gains)
+                        .map(|(filter, gain)| gain * filter.process(source))
+                        .sum(),
+                    Topology::Cascade => resonators
+                        .iter_mut()
+                        .fold(source, |x, resonator| resonator.process(x)),
+                }
+            })
+            .collect();
+
+        let peak = points.iter().fold(0.0f32, |a, x| a.max(x.abs()));
+        if peak > 0.0 {
+            for x in &mut points {
+                *x *= self.amplitude / peak;
+            }
+        }
+        Samples::new(SampleType::Pointsf32(points)).with_sample_rate(sample_rate)
+    }
+}
+
+/// Rosenberg glottal flow over one period, opening for 40% and closing for 16% of it
+fn glottal_pulse(phase: f32) -> f32 {
+    const OPEN: f32 = 0.4;
+    const CLOSE: f32 = 0.16;
+    if phase < OPEN {
+        0.5 * (1.0 - (PI * phase / OPEN).cos())
+    } else if phase < OPEN + CLOSE {
+        (PI * (phase - OPEN) / (2.0 * CLOSE)).cos()
+    } else {
+        0.0
+    }
+}
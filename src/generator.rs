@@ -1,9 +1,10 @@
 use minimp3::{Decoder as Mp3Decoder, Error as Mp3Error, Frame as Mp3Frame};
-use spectrum_analyzer::scaling::scale_to_zero_to_one;
+use spectrum_analyzer::scaling::{divide_by_N, scale_to_zero_to_one};
+use spectrum_analyzer::windows::hann_window;
 use spectrum_analyzer::{samples_fft_to_spectrum, FrequencyLimit};
 use std::fs::File;
 
-use crate::Note;
+use crate::{Note, Samples, Wave};
 
 pub fn play_notes(
     file: &'static str,
@@ -11,6 +12,16 @@ pub fn play_notes(
     max_freq: f32,
     npb: u32,
 ) -> Result<Vec<Note>, Box<dyn std::error::Error>> {
+    play_notes_with_frame(file, min_freq, max_freq, npb).map(|(notes, _)| notes)
+}
+
+/// Like [`play_notes`], also giving the length of each frame in seconds
+pub fn play_notes_with_frame(
+    file: &'static str,
+    min_freq: f32,
+    max_freq: f32,
+    npb: u32,
+) -> Result<(Vec<Note>, f64), Box<dyn std::error::Error>> {
     let (samples, sampling_rate) = read_mp3_to_mono(file);
 
     // Hann Window code in lib
@@ -47,7 +58,14 @@ pub fn play_notes(
         })
         .collect();
 
-    Ok(notes)
+    Ok((notes, frame_seconds(npb, sampling_rate)))
+}
+
+/// Length in seconds of the audio behind each note [`play_notes`] returns for `npb`
+/// at `sample_rate`
+pub fn frame_seconds(npb: u32, sample_rate: u32) -> f64 {
+    let step = 2u64.pow(npb.saturating_sub(13));
+    (2u64.pow(npb) * step) as f64 / sample_rate as f64
 }
 
 pub fn read_mp3_to_mono(file: &str) -> (Vec<i16>, u32) {
@@ -84,3 +102,82 @@ pub fn read_mp3_to_mono(file: &str) -> (Vec<i16>, u32) {
 
     (mono_samples, sampling_rate as u32)
 }
+
+/// A partial of an analytic wave next to what the FFT found at its frequency
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct PartialComparison {
+    pub frequency: f32,
+    pub expected: f32,
+    pub measured: f32,
+}
+
+impl PartialComparison {
+    pub fn error_db(&self) -> f32 {
+        20.0 * (self.measured / self.expected).log10()
+    }
+}
+
+/// Runs the first `fft_size` points of `samples` through a Hann windowed FFT and compares the
+/// amplitude at every partial of `wave` with the exact one from [`Wave::spectrum`].
+/// Each amplitude is measured from the power in the main lobe around the partial, so it doesn't
+/// depend on where the partial falls between bins. Partials need to be at least 4 bins apart.
+/// `fft_size` has to be a power of two no larger than 16384
+pub fn compare_spectrum(
+    wave: &Wave,
+    samples: &Samples,
+    fft_size: usize,
+) -> Result<Vec<PartialComparison>, Box<dyn std::error::Error>> {
+    let points: Vec<f32> = (0..fft_size)
+        .map(|i| {
+            samples
+                .sample_f32(i)
+                .ok_or("Not enough samples for the FFT")
+        })
+        .collect::<Result<_, _>>()?;
+    let sampling_rate = samples.sample_rate();
+    let spectrum = samples_fft_to_spectrum(
+        &hann_window(&points),
+        sampling_rate,
+        FrequencyLimit::All,
+        Some(&divide_by_N),
+    )
+    .map_err(|e| format!("{:?}", e))?;
+
+    let resolution = spectrum.frequency_resolution();
+    let data = spectrum.data();
+    Ok(wave
+        .spectrum()
+        .iter()
+        .filter(|line| line.frequency > 0.0 && line.frequency < sampling_rate as f32 / 2.0)
+        .map(|line| {
+            // The Hann main lobe is 4 bins wide. After the window and the scaling by N, the power
+            // of a sine of amplitude A in it adds up to 3 A^2 / 32, wherever the sine falls
+            let power: f32 = data
+                .iter()
+                .filter(|(f, _)| (f.val() - line.frequency).abs() < 2.0 * resolution)
+                .map(|(_, value)| value.val().powi(2))
+                .sum();
+            PartialComparison {
+                frequency: line.frequency,
+                expected: line.magnitude,
+                measured: (power * 32.0 / 3.0).sqrt(),
+            }
+        })
+        .collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{SampleType, Timbre, A4};
+
+    #[test]
+    fn exact_renders_compare_equal() {
+        let wave = Timbre::clarinet().wave(A4);
+        let points = (0..16384).map(|i| wave.at(i as f32 / 44100.0)).collect();
+        let samples = Samples::new(SampleType::Pointsf32(points)).with_sample_rate(44100);
+        for partial in compare_spectrum(&wave, &samples, 16384).unwrap() {
+            assert!(partial.error_db().abs() < 0.05, "{:?}", partial);
+        }
+    }
+}
@@ -0,0 +1,166 @@
+use crate::{Note, NoteEvent, Rng, Sequence, TempoMap};
+
+/// Random timing and velocity variation, the same every time for a given seed
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Humanize {
+    /// Largest shift of a note start, in seconds
+    pub timing: f32,
+    /// Largest change in velocity, as a fraction of it
+    pub velocity: f32,
+    pub seed: u64,
+}
+
+impl Default for Humanize {
+    fn default() -> Humanize {
+        Humanize {
+            timing: 0.01,
+            velocity: 0.1,
+            seed: 0,
+        }
+    }
+}
+
+impl Humanize {
+    pub fn new(timing: f32, velocity: f32) -> Humanize {
+        Humanize {
+            timing,
+            velocity,
+            seed: 0,
+        }
+    }
+
+    pub fn with_seed(mut self, seed: u64) -> Humanize {
+        self.seed = seed;
+        self
+    }
+
+    /// Moves every note by up to `timing` either way, never before the start, and keeps its length
+    pub fn apply(&self, sequence: &mut Sequence) {
+        let mut rng = Rng::new(self.seed);
+        for event in sequence.events_mut() {
+            event.start = (event.start + (rng.next_signed() * self.timing) as f64).max(0.0);
+            event.velocity =
+                (event.velocity * (1.0 + rng.next_signed() * self.velocity)).clamp(0.0, 1.0);
+        }
+        sequence.sort();
+    }
+}
+
+/// Snaps notes towards a grid
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Quantize {
+    /// Grid spacing in quarter notes, 0.25 for sixteenths
+    pub grid: f64,
+    /// How far notes move towards the grid, from 0 (not at all) to 1 (onto it)
+    pub strength: f32,
+    /// Quantizes note ends too, keeping every note at least one grid step long at full strength
+    pub ends: bool,
+}
+
+impl Quantize {
+    /// # Panics
+    /// Panics if `grid` is not positive
+    #[track_caller]
+    pub fn new(grid: f64) -> Quantize {
+        assert!(grid > 0.0, "Grid spacing must be positive");
+        Quantize {
+            grid,
+            strength: 1.0,
+            ends: false,
+        }
+    }
+
+    pub fn with_strength(mut self, strength: f32) -> Quantize {
+        self.strength = strength.clamp(0.0, 1.0);
+        self
+    }
+
+    pub fn with_ends(mut self, ends: bool) -> Quantize {
+        self.ends = ends;
+        self
+    }
+
+    fn snap(&self, quarters: f64) -> f64 {
+        let target = (quarters / self.grid).round() * self.grid;
+        quarters + (target - quarters) * self.strength as f64
+    }
+
+    pub fn apply(&self, sequence: &mut Sequence, tempo: &TempoMap) {
+        for event in sequence.events_mut() {
+            let start = tempo.quarters_at_seconds(event.start);
+            let end = tempo.quarters_at_seconds(event.end());
+            let snapped = self.snap(start);
+            let end = if self.ends {
+                let minimum = snapped + self.grid * self.strength as f64;
+                self.snap(end).max(minimum)
+            } else {
+                snapped + end - start
+            };
+            let start_seconds = tempo.seconds_at_quarters(snapped);
+            event.start = start_seconds;
+            event.duration = tempo.seconds_at_quarters(end) - start_seconds;
+        }
+        sequence.sort();
+    }
+}
+
+/// A timing and accent feel repeating every few grid steps, like a drummer's swing
+#[derive(Clone, Debug, PartialEq)]
+pub struct Groove {
+    /// Grid spacing in quarter notes
+    pub step: f64,
+    /// Shift of each step as a fraction of `step`, and the velocity scale for it
+    pub steps: Vec<(f32, f32)>,
+}
+
+impl Groove {
+    /// # Panics
+    /// Panics if `step` is not positive or `steps` is empty
+    #[track_caller]
+    pub fn new(step: f64, steps: Vec<(f32, f32)>) -> Groove {
+        assert!(step > 0.0, "Grid spacing must be positive");
+        assert!(!steps.is_empty(), "A groove needs at least one step");
+        Groove { step, steps }
+    }
+
+    /// Delays every second step, with `swing` in percent like [`Pattern`](crate::Pattern):
+    /// 50 is straight and 66 is triplet swing
+    pub fn swing(step: f64, swing: f32) -> Groove {
+        let delay = 2.0 * swing.clamp(50.0, 75.0) / 100.0 - 1.0;
+        Groove::new(step, vec![(0.0, 1.0), (delay, 1.0)])
+    }
+
+    /// Moves every note by the groove of its nearest step, scaled by `strength` from 0 to 1,
+    /// keeping its length
+    pub fn apply(&self, sequence: &mut Sequence, tempo: &TempoMap, strength: f32) {
+        for event in sequence.events_mut() {
+            let start = tempo.quarters_at_seconds(event.start);
+            let index = (start / self.step).round().max(0.0) as usize;
+            let (shift, accent) = self.steps[index % self.steps.len()];
+            let start = start + (shift * strength) as f64 * self.step;
+            event.start = tempo.seconds_at_quarters(start.max(0.0));
+            event.velocity = (event.velocity * (1.0 + (accent - 1.0) * strength)).clamp(0.0, 1.0);
+        }
+        sequence.sort();
+    }
+}
+
+/// Turns the frame-by-frame notes from [`play_notes`](crate::play_notes) into a sequence,
+/// joining repeated frames into one note. `frame` is the length of a frame in seconds, as given
+/// by [`play_notes_with_frame`](crate::play_notes_with_frame) or
+/// [`frame_seconds`](crate::frame_seconds)
+pub fn transcription(notes: &[Note], frame: f64, velocity: f32) -> Sequence {
+    let mut sequence = Sequence::new();
+    let mut current: Option<NoteEvent> = None;
+    for (i, &note) in notes.iter().enumerate() {
+        match &mut current {
+            Some(event) if event.note == note => event.duration += frame,
+            _ => {
+                sequence.events_mut().extend(current.take());
+                current = Some(NoteEvent::new(note, i as f64 * frame, frame, velocity));
+            }
+        }
+    }
+    sequence.events_mut().extend(current);
+    sequence
+}
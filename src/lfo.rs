@@ -0,0 +1,164 @@
+use std::f32::consts::TAU;
+
+use crate::{AutomatedWave, Channel, PanLaw, Wave};
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum LfoShape {
+    Sine,
+    Triangle,
+    Square,
+    SawUp,
+    SawDown,
+}
+
+impl LfoShape {
+    /// Value of the shape at `phase` (in cycles), between -1 and 1
+    pub fn at(&self, phase: f32) -> f32 {
+        let phase = phase.rem_euclid(1.0);
+        match self {
+            LfoShape::Sine => (TAU * phase).sin(),
+            LfoShape::Triangle => 4.0 * ((phase - 0.25).rem_euclid(1.0) - 0.5).abs() - 1.0,
+            LfoShape::Square => {
+                if phase < 0.5 {
+                    1.0
+                } else {
+                    -1.0
+                }
+            }
+            LfoShape::SawUp => 2.0 * phase - 1.0,
+            LfoShape::SawDown => 1.0 - 2.0 * phase,
+        }
+    }
+}
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Lfo {
+    pub shape: LfoShape,
+    pub rate: f32,
+    pub depth: f32,
+    pub phase: f32,
+}
+
+impl Lfo {
+    pub fn new(shape: LfoShape, rate: f32, depth: f32) -> Lfo {
+        Lfo {
+            shape,
+            rate,
+            depth,
+            phase: 0.0,
+        }
+    }
+
+    pub fn with_phase(mut self, phase: f32) -> Lfo {
+        self.phase = phase;
+        self
+    }
+
+    /// Scaled output at `x` seconds, between -depth and depth
+    pub fn at(&self, x: f32) -> f32 {
+        self.depth * self.shape.at(self.rate * x + self.phase)
+    }
+}
+
+/// A wave with LFOs routed to its amplitude, pitch and stereo position.
+/// Turn it into an [`AutomatedWave`] to combine the LFOs with automation
+#[derive(Clone, Debug, PartialEq)]
+pub struct ModulatedWave {
+    pub wave: Wave,
+    /// Depth from 0 (none) to 1 (full silence at the troughs)
+    pub tremolo: Option<Lfo>,
+    /// Depth in cents
+    pub vibrato: Option<Lfo>,
+    /// Depth from 0 (centered) to 1 (hard left to hard right)
+    pub auto_pan: Option<Lfo>,
+    pub pan_law: PanLaw,
+}
+
+impl ModulatedWave {
+    pub fn new(wave: Wave) -> ModulatedWave {
+        ModulatedWave {
+            wave,
+            tremolo: None,
+            vibrato: None,
+            auto_pan: None,
+            pan_law: PanLaw::ConstantPower,
+        }
+    }
+
+    pub fn with_tremolo(mut self, lfo: Lfo) -> ModulatedWave {
+        self.tremolo = Some(lfo);
+        self
+    }
+
+    pub fn with_vibrato(mut self, lfo: Lfo) -> ModulatedWave {
+        self.vibrato = Some(lfo);
+        self
+    }
+
+    pub fn with_auto_pan(mut self, lfo: Lfo) -> ModulatedWave {
+        self.auto_pan = Some(lfo);
+        self
+    }
+
+    pub fn with_pan_law(mut self, pan_law: PanLaw) -> ModulatedWave {
+        self.pan_law = pan_law;
+        self
+    }
+
+    /// Tremolo gain at `x` seconds
+    pub(crate) fn gain_at(&self, x: f32) -> f32 {
+        match self.tremolo {
+            Some(lfo) => 1.0 - lfo.depth * (1.0 - lfo.shape.at(lfo.rate * x + lfo.phase)) / 2.0,
+            None => 1.0,
+        }
+    }
+
+    /// Vibrato frequency ratio at `x` seconds
+    pub(crate) fn ratio_at(&self, x: f32) -> f32 {
+        match self.vibrato {
+            Some(lfo) => 2f32.powf(lfo.at(x) / 1200.0),
+            None => 1.0,
+        }
+    }
+
+    /// Auto-pan position at `x` seconds
+    pub(crate) fn pan_at(&self, x: f32) -> f32 {
+        self.auto_pan.map_or(0.0, |lfo| lfo.at(x))
+    }
+
+    /// Renders the wave, returning a stereo channel only if auto-pan is set
+    pub fn render(&self, sample_rate: u32, seconds: f32) -> Channel {
+        AutomatedWave::from(self.clone()).render(sample_rate, seconds)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::SineWave;
+
+    /// Largest difference from `expected` over the last 1000 samples of an 18 second render
+    fn end_error(wave: &ModulatedWave, expected: impl Fn(f64) -> f64) -> f64 {
+        let sample_rate = 44100;
+        let Channel::Mono(samples) = wave.render(sample_rate, 18.0) else {
+            panic!("Expected a mono render");
+        };
+        let count = samples.sample_count().unwrap();
+        (count - 1000..count)
+            .map(|i| {
+                let x = i as f64 / sample_rate as f64;
+                (samples.sample_f32(i).unwrap() as f64 - expected(x)).abs()
+            })
+            .fold(0.0, f64::max)
+    }
+
+    #[test]
+    fn long_renders_keep_their_pitch() {
+        let sine = |x: f64| (std::f64::consts::TAU * 440.0 * x).sin();
+        let wave = ModulatedWave::new(Wave::from(SineWave::with_frequency(440.0)))
+            .with_tremolo(Lfo::new(LfoShape::Sine, 5.0, 0.0));
+        assert!(end_error(&wave, sine) < 0.01);
+        let wave = wave.with_vibrato(Lfo::new(LfoShape::Sine, 5.0, 0.0));
+        assert!(end_error(&wave, sine) < 0.01);
+    }
+}
@@ -1,9 +1,58 @@
+pub mod arpeggiator;
+pub mod automation;
+pub mod chiptune;
+pub mod drums;
+pub mod envelope;
+pub mod filter;
+pub mod formant;
 pub mod generator;
+pub mod humanize;
+pub mod lfo;
+pub mod metronome;
+pub mod mixer;
+pub mod noise;
 pub mod note;
+pub mod pattern;
+pub mod physical;
+pub mod rhythm;
+pub mod score;
+pub mod sequencer;
+pub mod shepard;
+pub mod sweep;
+pub mod tempo;
+pub mod timbre;
+pub mod voice;
 pub mod wav;
 pub mod wave;
+pub mod wavetable;
 
-pub use generator::play_notes;
+pub use arpeggiator::{ArpMode, Arpeggiator};
+pub use automation::{AutomatedWave, Automation, Breakpoint, CurveShape};
+pub use chiptune::{Duty, NoiseChannel, PulseChannel, TriangleChannel, WaveChannel, WaveVolume};
+pub use drums::{Clap, DrumKit, HiHat, Kick, Snare, Tom};
+pub use envelope::Envelope;
+pub use filter::{Biquad, Filter, FilterType};
+pub use formant::{Formant, FormantSynth, Topology, Vowel};
+pub use generator::{
+    compare_spectrum, frame_seconds, play_notes, play_notes_with_frame, PartialComparison,
+};
+pub use humanize::{transcription, Groove, Humanize, Quantize};
+pub use lfo::{Lfo, LfoShape, ModulatedWave};
+pub use metronome::{Click, Metronome};
+pub use mixer::{MixDown, Mixer, PanLaw, Track};
+pub use noise::{Noise, NoiseColor, NoiseGenerator, Rng};
 pub use note::*;
-pub use wav::{Channel, SampleType, Samples, WavAudio, Error, Result};
-pub use wave::{SineWave, Wave};
+pub use pattern::{Lane, Pattern, Song, Step};
+pub use physical::{Flute, KarplusStrong};
+pub use rhythm::{euclidean, Polymeter, Polyrhythm};
+pub use sequencer::{NoteEvent, Sequence};
+pub use shepard::{Direction, Shepard};
+pub use sweep::{Portamento, Sweep, SweepKind};
+pub use tempo::{Position, TempoMap, TimeSignature};
+pub use timbre::{Partial, Timbre};
+pub use voice::{
+    Instrument, OneShot, StealPolicy, Synth, SynthVoice, Voice, VoiceEvent, VoiceManager,
+};
+pub use wav::{Channel, Error, Result, SampleType, Samples, WavAudio};
+pub use wave::{SineWave, SpectralLine, Wave};
+pub use wavetable::{Wavetable, WavetableOscillator};
@@ -0,0 +1,212 @@
+use std::f32::consts::TAU;
+
+use crate::{
+    mixer::{MixDown, Mixer, Track},
+    Channel, Position, SampleType, Samples, TempoMap, WavAudio,
+};
+
+/// A short decaying sine blip
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Click {
+    pub frequency: f32,
+    pub amplitude: f32,
+    /// In seconds
+    pub length: f32,
+}
+
+impl Click {
+    pub fn new(frequency: f32, amplitude: f32) -> Click {
+        Click {
+            frequency,
+            amplitude,
+            length: 0.03,
+        }
+    }
+
+    fn at(&self, x: f32) -> f32 {
+        // Down to about -60dB by the end
+        self.amplitude * (-7.0 * x / self.length).exp() * (TAU * self.frequency * x).sin()
+    }
+}
+
+/// Click track following the tempo and meter of a [`TempoMap`]
+#[derive(Clone, Debug, PartialEq)]
+pub struct Metronome {
+    pub downbeat: Click,
+    pub beat: Click,
+    pub subdivision: Click,
+    /// Plays the first beat of every bar with `downbeat`
+    pub accent: bool,
+    /// Clicks per beat, 1 plays only the beats
+    pub subdivisions: u32,
+    /// Bars counted in before the piece, at its first tempo and meter
+    pub count_in: u32,
+    /// Level of the click in dB when mixed under a track
+    pub gain: f32,
+}
+
+impl Default for Metronome {
+    fn default() -> Metronome {
+        Metronome {
+            downbeat: Click::new(1500.0, 0.9),
+            beat: Click::new(1000.0, 0.6),
+            subdivision: Click::new(800.0, 0.3),
+            accent: true,
+            subdivisions: 1,
+            count_in: 0,
+            gain: -6.0,
+        }
+    }
+}
+
+impl Metronome {
+    pub fn new() -> Metronome {
+        Metronome::default()
+    }
+
+    /// # Panics
+    /// Panics if `subdivisions` is 0
+    #[track_caller]
+    pub fn with_subdivisions(mut self, subdivisions: u32) -> Metronome {
+        assert!(subdivisions > 0, "A beat needs at least one click");
+        self.subdivisions = subdivisions;
+        self
+    }
+
+    pub fn with_count_in(mut self, bars: u32) -> Metronome {
+        self.count_in = bars;
+        self
+    }
+
+    pub fn with_accent(mut self, accent: bool) -> Metronome {
+        self.accent = accent;
+        self
+    }
+
+    pub fn with_gain(mut self, gain: f32) -> Metronome {
+        self.gain = gain;
+        self
+    }
+
+    /// Length of the count-in in seconds
+    pub fn count_in_seconds(&self, tempo: &TempoMap) -> f64 {
+        let start = Position::new(1, 1, 0);
+        let quarters = self.count_in as f64 * tempo.signature_at(1).bar_length();
+        quarters * 60.0 / tempo.bpm_at(start)
+    }
+
+    /// Every click in the first `seconds` of the piece with its time, count-in included
+    pub fn clicks(&self, tempo: &TempoMap, seconds: f32) -> Vec<(f64, Click)> {
+        let offset = self.count_in_seconds(tempo);
+        let signature = tempo.signature_at(1);
+        let beat = signature.beat_length() * 60.0 / tempo.bpm_at(Position::new(1, 1, 0));
+        let mut clicks = Vec::new();
+        for i in 0..self.count_in * signature.beats {
+            for sub in 0..self.subdivisions {
+                let time = (i as f64 + sub as f64 / self.subdivisions as f64) * beat;
+                clicks.push((time, self.click(i % signature.beats, sub)));
+            }
+        }
+
+        for bar in 1.. {
+            let signature = tempo.signature_at(bar);
+            let start = tempo.quarters(Position::new(bar, 1, 0));
+            if tempo.seconds_at_quarters(start) >= seconds as f64 {
+                break;
+            }
+            for i in 0..signature.beats {
+                for sub in 0..self.subdivisions {
+                    let quarters = start
+                        + (i as f64 + sub as f64 / self.subdivisions as f64)
+                            * signature.beat_length();
+                    let time = tempo.seconds_at_quarters(quarters);
+                    if time < seconds as f64 {
+                        clicks.push((offset + time, self.click(i, sub)));
+                    }
+                }
+            }
+        }
+        clicks
+    }
+
+    fn click(&self, beat: u32, subdivision: u32) -> Click {
+        match (beat, subdivision) {
+            (_, 1..) => self.subdivision,
+            (0, _) if self.accent => self.downbeat,
+            _ => self.beat,
+        }
+    }
+
+    /// Renders the click track for the first `seconds` of the piece, with the count-in before it
+    pub fn render(&self, tempo: &TempoMap, sample_rate: u32, seconds: f32) -> WavAudio {
+        WavAudio::mono(self.render_samples(tempo, sample_rate, seconds))
+    }
+
+    fn render_samples(&self, tempo: &TempoMap, sample_rate: u32, seconds: f32) -> Samples {
+        let total = self.count_in_seconds(tempo) as f32 + seconds;
+        let mut points = vec![0.0; (sample_rate as f32 * total) as usize];
+        for (time, click) in self.clicks(tempo, seconds) {
+            let start = (time * sample_rate as f64).round() as usize;
+            let length = (click.length * sample_rate as f32) as usize;
+            for (j, x) in points.iter_mut().skip(start).take(length).enumerate() {
+                *x += click.at(j as f32 / sample_rate as f32);
+            }
+        }
+        Samples::new(SampleType::Pointsf32(points)).with_sample_rate(sample_rate)
+    }
+
+    /// Mixes the click track at `self.gain` under `track`, which is delayed by the count-in.
+    /// A muted track stays muted; soloing it has no effect, so the click is always heard
+    pub fn mix_under(&self, tempo: &TempoMap, track: &Track, seconds: f32) -> MixDown {
+        let sample_rate = track.channel().sample_rate();
+        let offset = (self.count_in_seconds(tempo) * sample_rate as f64).round() as usize;
+        let count = offset + (sample_rate as f32 * seconds) as usize;
+        let delay = |samples: &Samples| {
+            let points = (0..count)
+                .map(|i| {
+                    i.checked_sub(offset)
+                        .and_then(|i| samples.sample_f32(i))
+                        .unwrap_or_default()
+                })
+                .collect();
+            Samples::new(SampleType::Pointsf32(points)).with_sample_rate(sample_rate)
+        };
+        let channel = match track.channel() {
+            Channel::Mono(samples) => Channel::Mono(delay(samples)),
+            Channel::Stereo(right, left) => Channel::Stereo(delay(right), delay(left)),
+        };
+
+        let mut delayed = Track::new(channel)
+            .with_gain(track.gain)
+            .with_pan(track.pan);
+        delayed.mute = track.mute;
+        let click =
+            Track::mono(self.render_samples(tempo, sample_rate, seconds)).with_gain(self.gain);
+
+        let mut mixer = Mixer::new(sample_rate);
+        mixer.add_track(delayed);
+        mixer.add_track(click);
+        mixer.mix_samples(count)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn mix_under_keeps_every_sample() {
+        let tempo = TempoMap::new(120.0);
+        let metronome = Metronome::new().with_count_in(1);
+        let silence = Samples::new(SampleType::Pointsf32(vec![0.0; 100])).with_sample_rate(44100);
+        for (seconds, count) in [(0.1, 92610), (3.7, 251370)] {
+            let track = Track::mono(silence.clone()).soloed();
+            let mut mix = metronome.mix_under(&tempo, &track, seconds);
+            assert!(mix.peak > 0.0);
+            let Channel::Stereo(right, _) = mix.audio.get_channel() else {
+                panic!("Expected a stereo mix");
+            };
+            assert_eq!(right.sample_count(), Some(count));
+        }
+    }
+}
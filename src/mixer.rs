@@ -0,0 +1,231 @@
+use std::f32::consts::FRAC_PI_4;
+
+use crate::{Channel, SampleType, Samples, WavAudio};
+
+/// How a mono track is spread over the two sides as it is panned
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum PanLaw {
+    /// -6dB in the center
+    Linear,
+    /// -3dB in the center, so the loudness stays the same across the field
+    ConstantPower,
+    /// -4.5dB in the center, halfway between the other two
+    Compromise,
+}
+
+impl PanLaw {
+    /// Gains for the right and left side at `pan`, from -1 (left) to 1 (right)
+    pub fn gains(&self, pan: f32) -> (f32, f32) {
+        let pan = pan.clamp(-1.0, 1.0);
+        let linear = ((1.0 + pan) / 2.0, (1.0 - pan) / 2.0);
+        let angle = (pan + 1.0) * FRAC_PI_4;
+        let power = (angle.sin(), angle.cos());
+        match self {
+            PanLaw::Linear => linear,
+            PanLaw::ConstantPower => power,
+            PanLaw::Compromise => ((linear.0 * power.0).sqrt(), (linear.1 * power.1).sqrt()),
+        }
+    }
+}
+
+pub fn db_to_gain(db: f32) -> f32 {
+    10f32.powf(db / 20.0)
+}
+
+pub fn gain_to_db(gain: f32) -> f32 {
+    20.0 * gain.log10()
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Track {
+    channel: Channel,
+    /// In dB
+    pub gain: f32,
+    /// From -1 (left) to 1 (right). Stereo tracks are balanced instead of panned
+    pub pan: f32,
+    pub mute: bool,
+    pub solo: bool,
+}
+
+impl Track {
+    pub fn new(channel: Channel) -> Track {
+        if let Channel::Stereo(r, l) = &channel {
+            assert_eq!(r.sample_rate(), l.sample_rate());
+        }
+        Track {
+            channel,
+            gain: 0.0,
+            pan: 0.0,
+            mute: false,
+            solo: false,
+        }
+    }
+
+    pub fn mono(samples: Samples) -> Track {
+        Track::new(Channel::Mono(samples))
+    }
+
+    pub fn stereo(right: Samples, left: Samples) -> Track {
+        Track::new(Channel::Stereo(right, left))
+    }
+
+    pub fn with_gain(mut self, gain: f32) -> Track {
+        self.gain = gain;
+        self
+    }
+
+    pub fn with_pan(mut self, pan: f32) -> Track {
+        self.pan = pan.clamp(-1.0, 1.0);
+        self
+    }
+
+    pub fn muted(mut self) -> Track {
+        self.mute = true;
+        self
+    }
+
+    pub fn soloed(mut self) -> Track {
+        self.solo = true;
+        self
+    }
+
+    pub fn channel(&self) -> &Channel {
+        &self.channel
+    }
+
+    /// Right and left sample `i` after gain and panning
+    fn frame(&self, i: usize, law: PanLaw) -> (f32, f32) {
+        let gain = db_to_gain(self.gain);
+        match &self.channel {
+            Channel::Mono(samples) => {
+                let x = gain * samples.sample_f32(i).unwrap_or_default();
+                let (right, left) = law.gains(self.pan);
+                (x * right, x * left)
+            }
+            Channel::Stereo(right, left) => {
+                let balance = ((1.0 + self.pan).min(1.0), (1.0 - self.pan).min(1.0));
+                (
+                    gain * balance.0 * right.sample_f32(i).unwrap_or_default(),
+                    gain * balance.1 * left.sample_f32(i).unwrap_or_default(),
+                )
+            }
+        }
+    }
+}
+
+/// Result of a mix, with the level it peaked at on the master bus
+pub struct MixDown {
+    pub audio: WavAudio,
+    pub peak: f32,
+}
+
+impl MixDown {
+    /// Distance from the peak to full scale in dB. Negative means the mix clips
+    pub fn headroom(&self) -> f32 {
+        -gain_to_db(self.peak)
+    }
+
+    pub fn is_clipping(&self) -> bool {
+        self.peak > 1.0
+    }
+}
+
+pub struct Mixer {
+    tracks: Vec<Track>,
+    sample_rate: u32,
+    /// Master bus gain in dB
+    pub master_gain: f32,
+    pub pan_law: PanLaw,
+}
+
+impl Mixer {
+    pub fn new(sample_rate: u32) -> Mixer {
+        Mixer {
+            tracks: Vec::new(),
+            sample_rate,
+            master_gain: 0.0,
+            pan_law: PanLaw::ConstantPower,
+        }
+    }
+
+    pub fn with_pan_law(mut self, pan_law: PanLaw) -> Mixer {
+        self.pan_law = pan_law;
+        self
+    }
+
+    pub fn with_master_gain(mut self, gain: f32) -> Mixer {
+        self.master_gain = gain;
+        self
+    }
+
+    /// Adds a track and returns its index
+    pub fn add_track(&mut self, track: Track) -> usize {
+        assert_eq!(track.channel.sample_rate(), self.sample_rate);
+        self.tracks.push(track);
+        self.tracks.len() - 1
+    }
+
+    pub fn tracks(&self) -> &[Track] {
+        &self.tracks
+    }
+
+    pub fn track_mut(&mut self, index: usize) -> Option<&mut Track> {
+        self.tracks.get_mut(index)
+    }
+
+    pub fn sample_rate(&self) -> u32 {
+        self.sample_rate
+    }
+
+    /// Length in seconds of the longest track, or `None` if a track never ends
+    pub fn duration(&self) -> Option<f32> {
+        self.tracks
+            .iter()
+            .map(|track| match &track.channel {
+                Channel::Mono(s) => s.sample_count(),
+                Channel::Stereo(r, l) => r
+                    .sample_count()
+                    .zip(l.sample_count())
+                    .map(|(r, l)| r.max(l)),
+            })
+            .try_fold(0, |longest, count| count.map(|c| longest.max(c)))
+            .map(|count| count as f32 / self.sample_rate as f32)
+    }
+
+    /// Mixes `seconds` of every audible track into a stereo master.
+    /// When any track is soloed, only soloed tracks are audible
+    pub fn mix(&self, seconds: f32) -> MixDown {
+        self.mix_samples((self.sample_rate as f32 * seconds) as usize)
+    }
+
+    /// Like [`Mixer::mix`], for an exact number of samples
+    pub fn mix_samples(&self, count: usize) -> MixDown {
+        let any_solo = self.tracks.iter().any(|t| t.solo);
+        let audible: Vec<&Track> = self
+            .tracks
+            .iter()
+            .filter(|t| !t.mute && (t.solo || !any_solo))
+            .collect();
+        let master = db_to_gain(self.master_gain);
+
+        let mut peak = 0.0f32;
+        let (right, left): (Vec<f32>, Vec<f32>) = (0..count)
+            .map(|i| {
+                let (r, l) = audible.iter().fold((0.0, 0.0), |(r, l), track| {
+                    let (tr, tl) = track.frame(i, self.pan_law);
+                    (r + tr, l + tl)
+                });
+                let (r, l) = (master * r, master * l);
+                peak = peak.max(r.abs()).max(l.abs());
+                (r, l)
+            })
+            .unzip();
+
+        let samples =
+            |points| Samples::new(SampleType::Pointsf32(points)).with_sample_rate(self.sample_rate);
+        MixDown {
+            audio: WavAudio::stereo(samples(right), samples(left)),
+            peak,
+        }
+    }
+}
@@ -0,0 +1,252 @@
+use crate::{Instrument, Note, NoteEvent, Rng, Samples, Sequence, TempoMap};
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Step {
+    pub velocity: f32,
+    /// Chance from 0 to 1 that the step plays each time round
+    pub probability: f32,
+}
+
+impl Step {
+    pub fn new(velocity: f32) -> Step {
+        Step {
+            velocity,
+            probability: 1.0,
+        }
+    }
+
+    pub fn with_probability(mut self, probability: f32) -> Step {
+        self.probability = probability.clamp(0.0, 1.0);
+        self
+    }
+}
+
+/// One row of the grid, playing a single note
+#[derive(Clone, Debug, PartialEq)]
+pub struct Lane {
+    pub note: Note,
+    steps: Vec<Option<Step>>,
+}
+
+impl Lane {
+    pub const VELOCITY: f32 = 0.7;
+    pub const ACCENT: f32 = 1.0;
+
+    pub fn new(note: Note, length: usize) -> Lane {
+        Lane {
+            note,
+            steps: vec![None; length],
+        }
+    }
+
+    /// Reads a row like `"x..x..x.X..?x..."`: `x` is a hit, `X` an accented hit,
+    /// `?` a hit that plays half the time and anything else is a rest
+    pub fn from_pattern(note: Note, pattern: &str) -> Lane {
+        Lane {
+            note,
+            steps: pattern
+                .chars()
+                .filter(|c| !c.is_whitespace() && *c != '|')
+                .map(|c| match c {
+                    'x' => Some(Step::new(Self::VELOCITY)),
+                    'X' => Some(Step::new(Self::ACCENT)),
+                    '?' => Some(Step::new(Self::VELOCITY).with_probability(0.5)),
+                    _ => None,
+                })
+                .collect(),
+        }
+    }
+
+    pub fn steps(&self) -> &[Option<Step>] {
+        &self.steps
+    }
+
+    /// Sets step `i`, growing the lane if needed
+    pub fn set(&mut self, i: usize, step: Option<Step>) {
+        if i >= self.steps.len() {
+            self.steps.resize(i + 1, None);
+        }
+        self.steps[i] = step;
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Pattern {
+    lanes: Vec<Lane>,
+    length: usize,
+    /// Steps in one quarter note, 4 for sixteenths
+    pub steps_per_beat: u32,
+    /// Where the second step of every pair falls, as a percentage of the pair.
+    /// 50 is straight, 66 is triplet swing
+    pub swing: f32,
+    /// Fraction of a step each note is held for
+    pub gate: f32,
+}
+
+impl Pattern {
+    pub fn new(length: usize) -> Pattern {
+        Pattern {
+            lanes: Vec::new(),
+            length: length.max(1),
+            steps_per_beat: 4,
+            swing: 50.0,
+            gate: 0.5,
+        }
+    }
+
+    pub fn with_lane(mut self, lane: Lane) -> Pattern {
+        self.add_lane(lane);
+        self
+    }
+
+    pub fn with_swing(mut self, swing: f32) -> Pattern {
+        self.swing = swing.clamp(50.0, 75.0);
+        self
+    }
+
+    pub fn with_steps_per_beat(mut self, steps_per_beat: u32) -> Pattern {
+        self.steps_per_beat = steps_per_beat.max(1);
+        self
+    }
+
+    pub fn with_gate(mut self, gate: f32) -> Pattern {
+        self.gate = gate;
+        self
+    }
+
+    /// Lanes longer than the pattern are cut off, shorter ones leave the rest silent
+    pub fn add_lane(&mut self, lane: Lane) {
+        self.lanes.push(lane);
+    }
+
+    pub fn lanes(&self) -> &[Lane] {
+        &self.lanes
+    }
+
+    pub fn lanes_mut(&mut self) -> &mut [Lane] {
+        &mut self.lanes
+    }
+
+    pub fn length(&self) -> usize {
+        self.length
+    }
+
+    pub fn set_length(&mut self, length: usize) {
+        self.length = length.max(1);
+    }
+
+    /// Length in quarter notes
+    pub fn quarters(&self) -> f64 {
+        self.length as f64 / self.steps_per_beat as f64
+    }
+
+    /// Start of step `i` in quarter notes, after swing
+    pub fn step_position(&self, i: usize) -> f64 {
+        let step = 1.0 / self.steps_per_beat as f64;
+        let pair = (i / 2) as f64 * 2.0 * step;
+        if i.is_multiple_of(2) {
+            pair
+        } else {
+            pair + 2.0 * step * self.swing as f64 / 100.0
+        }
+    }
+
+    /// Times the pattern starting `offset` quarter notes in, rolling the dice for every step with a probability
+    pub fn events(&self, tempo: &TempoMap, offset: f64, rng: &mut Rng) -> Vec<NoteEvent> {
+        let step = 1.0 / self.steps_per_beat as f64;
+        let mut events = Vec::new();
+        for i in 0..self.length {
+            for lane in &self.lanes {
+                let Some(Some(hit)) = lane.steps.get(i) else {
+                    continue;
+                };
+                if hit.probability < 1.0 && rng.next_f32() >= hit.probability {
+                    continue;
+                }
+                let start = offset + self.step_position(i);
+                let end = start + step * self.gate as f64;
+                let start_seconds = tempo.seconds_at_quarters(start);
+                events.push(NoteEvent::new(
+                    lane.note,
+                    start_seconds,
+                    tempo.seconds_at_quarters(end) - start_seconds,
+                    hit.velocity,
+                ));
+            }
+        }
+        events
+    }
+}
+
+/// Patterns chained one after another
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Song {
+    patterns: Vec<Pattern>,
+    /// Index into `patterns` for every slot of the song
+    order: Vec<usize>,
+    pub seed: u64,
+}
+
+impl Song {
+    pub fn new() -> Song {
+        Song::default()
+    }
+
+    pub fn with_seed(mut self, seed: u64) -> Song {
+        self.seed = seed;
+        self
+    }
+
+    /// Adds a pattern to the song, played `times` times in a row, and returns its index
+    pub fn add_pattern(&mut self, pattern: Pattern, times: usize) -> usize {
+        self.patterns.push(pattern);
+        let index = self.patterns.len() - 1;
+        self.repeat(index, times);
+        index
+    }
+
+    /// Plays an already added pattern again
+    /// # Panics
+    /// Panics if there is no pattern at `index`
+    #[track_caller]
+    pub fn repeat(&mut self, index: usize, times: usize) {
+        assert!(index < self.patterns.len(), "No pattern at index {}", index);
+        self.order.extend(std::iter::repeat_n(index, times));
+    }
+
+    pub fn then(mut self, pattern: Pattern, times: usize) -> Song {
+        self.add_pattern(pattern, times);
+        self
+    }
+
+    pub fn patterns(&self) -> &[Pattern] {
+        &self.patterns
+    }
+
+    pub fn order(&self) -> &[usize] {
+        &self.order
+    }
+
+    pub fn sequence(&self, tempo: &TempoMap) -> Sequence {
+        let mut rng = Rng::new(self.seed);
+        let mut offset = 0.0;
+        let mut sequence = Sequence::new();
+        for &index in &self.order {
+            let pattern = &self.patterns[index];
+            for event in pattern.events(tempo, offset, &mut rng) {
+                sequence.add(event);
+            }
+            offset += pattern.quarters();
+        }
+        sequence
+    }
+
+    pub fn render<I: Instrument>(
+        &self,
+        instrument: &I,
+        tempo: &TempoMap,
+        sample_rate: u32,
+    ) -> Samples {
+        self.sequence(tempo).render(instrument, sample_rate)
+    }
+}
@@ -0,0 +1,223 @@
+use crate::{noise::Rng, Note, SampleType, Samples};
+
+/// Plucked string after Karplus and Strong: a burst of noise circulating in a damped delay line
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct KarplusStrong {
+    frequency: f32,
+    pub amplitude: f32,
+    /// Time in seconds for the string to fall by 60dB
+    pub decay: f32,
+    /// From 0 (dull, strongly damped highs) to 1 (no damping)
+    pub brightness: f32,
+    /// Seeds the [`Rng`] for the initial burst, so equal seeds give identical plucks
+    pub seed: u64,
+}
+
+impl KarplusStrong {
+    pub fn new(note: Note) -> KarplusStrong {
+        KarplusStrong::with_frequency(note.frequency()).with_amplitude(note.amplitude())
+    }
+
+    pub fn with_frequency(frequency: f32) -> KarplusStrong {
+        KarplusStrong {
+            frequency,
+            amplitude: 1.0,
+            decay: 2.0,
+            brightness: 0.5,
+            seed: 1,
+        }
+    }
+
+    pub fn with_amplitude(mut self, amplitude: f32) -> KarplusStrong {
+        self.amplitude = amplitude;
+        self
+    }
+
+    pub fn with_decay(mut self, decay: f32) -> KarplusStrong {
+        self.decay = decay;
+        self
+    }
+
+    pub fn with_brightness(mut self, brightness: f32) -> KarplusStrong {
+        self.brightness = brightness.clamp(0.0, 1.0);
+        self
+    }
+
+    pub fn with_seed(mut self, seed: u64) -> KarplusStrong {
+        self.seed = seed;
+        self
+    }
+
+    pub fn frequency(&self) -> f32 {
+        self.frequency
+    }
+
+    pub fn render(&self, sample_rate: u32, seconds: f32) -> Samples {
+        let count = (sample_rate as f32 * seconds) as usize;
+        let mut rng = Rng::new(self.seed);
+
+        // The two point damping filter delays the loop by `s` samples and the allpass makes up
+        // the fractional part of the period, so the string stays in tune
+        let s = (1.0 - self.brightness) / 2.0;
+        let gain = 0.001f32.powf(1.0 / (self.frequency * self.decay.max(f32::EPSILON)));
+        let delay = (sample_rate as f32 / self.frequency - s).max(2.0);
+        let mut len = delay.floor();
+        if delay - len < 0.1 {
+            len -= 1.0;
+        }
+        let fraction = delay - len;
+        let c = (1.0 - fraction) / (1.0 + fraction);
+
+        let mut buffer: Vec<f32> = (0..len as usize)
+            .map(|_| self.amplitude * rng.next_signed())
+            .collect();
+        let mut index = 0;
+        let mut previous = 0.0;
+        let (mut ap_x, mut ap_y) = (0.0, 0.0);
+
+        let points = (0..count)
+            .map(|_| {
+                let out = buffer[index];
+                let damped = gain * ((1.0 - s) * out + s * previous);
+                previous = out;
+                ap_y = c * damped + ap_x - c * ap_y;
+                ap_x = damped;
+                buffer[index] = ap_y;
+                index = (index + 1) % buffer.len();
+                out
+            })
+            .collect();
+        Samples::new(SampleType::Pointsf32(points)).with_sample_rate(sample_rate)
+    }
+}
+
+/// Waveguide flute after Cook's slide flute: a jet delay with a cubic nonlinearity driving a bore delay
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Flute {
+    frequency: f32,
+    pub amplitude: f32,
+    /// Amount of breath noise, relative to the breath pressure
+    pub noise: f32,
+    pub attack: f32,
+    pub release: f32,
+    /// Seeds the [`Rng`] for the breath noise
+    pub seed: u64,
+}
+
+impl Flute {
+    const JET_RATIO: f32 = 0.32;
+    const JET_REFLECTION: f32 = 0.5;
+    const END_REFLECTION: f32 = 0.5;
+
+    pub fn new(note: Note) -> Flute {
+        Flute::with_frequency(note.frequency()).with_amplitude(note.amplitude())
+    }
+
+    pub fn with_frequency(frequency: f32) -> Flute {
+        Flute {
+            frequency,
+            amplitude: 1.0,
+            noise: 0.15,
+            attack: 0.05,
+            release: 0.1,
+            seed: 1,
+        }
+    }
+
+    pub fn with_amplitude(mut self, amplitude: f32) -> Flute {
+        self.amplitude = amplitude;
+        self
+    }
+
+    pub fn with_noise(mut self, noise: f32) -> Flute {
+        self.noise = noise;
+        self
+    }
+
+    pub fn with_envelope(mut self, attack: f32, release: f32) -> Flute {
+        self.attack = attack;
+        self.release = release;
+        self
+    }
+
+    pub fn with_seed(mut self, seed: u64) -> Flute {
+        self.seed = seed;
+        self
+    }
+
+    pub fn frequency(&self) -> f32 {
+        self.frequency
+    }
+
+    pub fn render(&self, sample_rate: u32, seconds: f32) -> Samples {
+        let rate = sample_rate as f32;
+        let count = (rate * seconds) as usize;
+        let mut rng = Rng::new(self.seed);
+
+        // The bore is tuned a fifth below the note, the jet overblows it up to the note
+        let bore_length = (rate / (self.frequency * 2.0 / 3.0)).max(1.0);
+        let mut bore = DelayLine::new(bore_length as usize + 2);
+        let mut jet = DelayLine::new((bore_length * Self::JET_RATIO) as usize + 2);
+
+        let pressure = 1.1 + 0.2 * self.amplitude;
+        let pole = 0.7 - 0.1 * 22050.0 / rate;
+        let (mut filtered, mut dc_x, mut dc_y) = (0.0, 0.0, 0.0);
+        let release_start = seconds - self.release;
+
+        let points = (0..count)
+            .map(|i| {
+                let x = i as f32 / rate;
+                let envelope = if x < self.attack {
+                    x / self.attack
+                } else if x > release_start {
+                    ((seconds - x) / self.release).max(0.0)
+                } else {
+                    1.0
+                };
+                let breath = envelope * pressure * (1.0 + self.noise * rng.next_signed());
+
+                // Lossy, inverting reflection at the open end of the bore, then a DC blocker
+                filtered = -(1.0 - pole) * bore.read(bore_length) + pole * filtered;
+                dc_y = filtered - dc_x + 0.995 * dc_y;
+                dc_x = filtered;
+
+                jet.write(breath - Self::JET_REFLECTION * dc_y);
+                let jet_out = jet.read(bore_length * Self::JET_RATIO);
+                let driven = (jet_out * (jet_out * jet_out - 1.0)).clamp(-1.0, 1.0);
+                bore.write(driven + Self::END_REFLECTION * dc_y);
+                0.3 * self.amplitude * bore.read(0.0)
+            })
+            .collect();
+        Samples::new(SampleType::Pointsf32(points)).with_sample_rate(sample_rate)
+    }
+}
+
+struct DelayLine {
+    buffer: Vec<f32>,
+    position: usize,
+}
+
+impl DelayLine {
+    fn new(len: usize) -> DelayLine {
+        DelayLine {
+            buffer: vec![0.0; len.max(2)],
+            position: 0,
+        }
+    }
+
+    fn write(&mut self, x: f32) {
+        self.position = (self.position + 1) % self.buffer.len();
+        self.buffer[self.position] = x;
+    }
+
+    /// Reads `delay` samples behind the last write, interpolating linearly
+    fn read(&self, delay: f32) -> f32 {
+        let len = self.buffer.len();
+        let delay = delay.clamp(0.0, (len - 2) as f32);
+        let whole = delay as usize;
+        let fraction = delay - whole as f32;
+        let a = self.buffer[(self.position + len - whole) % len];
+        let b = self.buffer[(self.position + len - whole - 1) % len];
+        a + (b - a) * fraction
+    }
+}
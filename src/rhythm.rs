@@ -0,0 +1,216 @@
+use crate::{Lane, Note, Pattern, Sequence, Step, TempoMap};
+
+/// The Euclidean rhythm E(`hits`, `steps`): `hits` onsets spread as evenly as possible over
+/// `steps`, starting on a hit and then rotated `rotation` steps to the right
+/// # Panics
+/// Panics if `steps` is 0 or `hits` is more than `steps`
+#[track_caller]
+pub fn euclidean(hits: usize, steps: usize, rotation: usize) -> Vec<bool> {
+    assert!(steps > 0, "A rhythm needs at least one step");
+    assert!(hits <= steps, "More hits than steps");
+    // Bjorklund's algorithm: keep pairing the remainder groups onto the leading ones
+    let mut groups = vec![vec![true]; hits];
+    let mut remainder = vec![vec![false]; steps - hits];
+    while remainder.len() > 1 && !groups.is_empty() {
+        let paired = groups.len().min(remainder.len());
+        let rest = if groups.len() > paired {
+            groups.split_off(paired)
+        } else {
+            remainder.split_off(paired)
+        };
+        for (group, tail) in groups.iter_mut().zip(remainder) {
+            group.extend(tail);
+        }
+        remainder = rest;
+    }
+    let mut rhythm: Vec<bool> = groups.into_iter().chain(remainder).flatten().collect();
+    rhythm.rotate_right(rotation % steps);
+    rhythm
+}
+
+impl Lane {
+    /// A lane hitting `note` on every `true` step
+    pub fn from_hits(note: Note, hits: &[bool], velocity: f32) -> Lane {
+        let mut lane = Lane::new(note, hits.len());
+        for (i, _) in hits.iter().enumerate().filter(|(_, &hit)| hit) {
+            lane.set(i, Some(Step::new(velocity)));
+        }
+        lane
+    }
+
+    pub fn euclidean(note: Note, hits: usize, steps: usize, rotation: usize) -> Lane {
+        Lane::from_hits(note, &euclidean(hits, steps, rotation), Lane::VELOCITY)
+    }
+}
+
+/// Cycles of different lengths on the same step grid, each looping on its own until they line up
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Polymeter {
+    cycles: Vec<Lane>,
+}
+
+impl Polymeter {
+    pub fn new() -> Polymeter {
+        Polymeter::default()
+    }
+
+    /// # Panics
+    /// Panics if `cycle` has no steps
+    #[track_caller]
+    pub fn with_cycle(mut self, cycle: Lane) -> Polymeter {
+        assert!(!cycle.steps().is_empty(), "A cycle needs at least one step");
+        self.cycles.push(cycle);
+        self
+    }
+
+    pub fn cycles(&self) -> &[Lane] {
+        &self.cycles
+    }
+
+    /// Steps until every cycle is back at its start
+    pub fn length(&self) -> usize {
+        self.cycles
+            .iter()
+            .map(|c| c.steps().len())
+            .fold(1, |a, b| a / gcd(a, b) * b)
+    }
+
+    /// One full period as a step pattern, every cycle repeated to fill it
+    pub fn pattern(&self) -> Pattern {
+        let length = self.length();
+        let mut pattern = Pattern::new(length);
+        for cycle in &self.cycles {
+            let mut lane = Lane::new(cycle.note, length);
+            for (i, step) in cycle.steps().iter().cycle().take(length).enumerate() {
+                lane.set(i, *step);
+            }
+            pattern.add_lane(lane);
+        }
+        pattern
+    }
+}
+
+/// Several even pulses played against each other over the same span, like 3 against 2
+#[derive(Clone, Debug, PartialEq)]
+pub struct Polyrhythm {
+    /// Length of the span in quarter notes
+    pub span: f64,
+    /// Note, number of even pulses in the span and velocity of each voice
+    voices: Vec<(Note, u32, f32)>,
+    /// Fraction of a pulse each note is held for
+    pub gate: f32,
+}
+
+impl Polyrhythm {
+    pub const MAX_SUBDIVISION: usize = 64;
+
+    pub fn new(span: f64) -> Polyrhythm {
+        Polyrhythm {
+            span,
+            voices: Vec::new(),
+            gate: 0.5,
+        }
+    }
+
+    /// # Panics
+    /// Panics if `pulses` is 0
+    #[track_caller]
+    pub fn with_voice(mut self, note: Note, pulses: u32, velocity: f32) -> Polyrhythm {
+        assert!(pulses > 0, "A voice needs at least one pulse");
+        self.voices.push((note, pulses, velocity));
+        self
+    }
+
+    pub fn with_gate(mut self, gate: f32) -> Polyrhythm {
+        self.gate = gate;
+        self
+    }
+
+    pub fn voices(&self) -> &[(Note, u32, f32)] {
+        &self.voices
+    }
+
+    /// The smallest step pattern holding every pulse exactly, spanning `span` quarter notes.
+    /// Spans that can't be fit on a grid of up to [`Polyrhythm::MAX_SUBDIVISION`] times the
+    /// pulses are rounded
+    pub fn pattern(&self) -> Pattern {
+        let pulses = self
+            .voices
+            .iter()
+            .fold(1, |a, &(_, b, _)| a / gcd(a, b as usize) * b as usize);
+        // Patterns need a whole number of steps per beat, so the grid is made finer until it fits
+        let length = (1..=Self::MAX_SUBDIVISION)
+            .map(|m| pulses * m)
+            .find(|&length| (length as f64 / self.span).fract().abs() < 1e-9)
+            .unwrap_or(pulses);
+        let mut pattern = Pattern::new(length);
+        pattern.steps_per_beat = (length as f64 / self.span).round().max(1.0) as u32;
+        pattern.gate = self.gate;
+        for &(note, pulses, velocity) in &self.voices {
+            let every = length / pulses as usize;
+            let hits: Vec<bool> = (0..length).map(|i| i % every == 0).collect();
+            pattern.add_lane(Lane::from_hits(note, &hits, velocity));
+        }
+        pattern
+    }
+
+    /// Plays the span `repeats` times from the start of `tempo`
+    pub fn sequence(&self, tempo: &TempoMap, repeats: u32) -> Sequence {
+        let mut sequence = Sequence::new();
+        for repeat in 0..repeats {
+            for &(note, pulses, velocity) in &self.voices {
+                let pulse = self.span / pulses as f64;
+                for i in 0..pulses {
+                    let start = repeat as f64 * self.span + i as f64 * pulse;
+                    let end = start + pulse * self.gate as f64;
+                    let start = tempo.seconds_at_quarters(start);
+                    let end = tempo.seconds_at_quarters(end);
+                    sequence.add_note(note, start, end - start, velocity);
+                }
+            }
+        }
+        sequence.sort();
+        sequence
+    }
+}
+
+fn gcd(a: usize, b: usize) -> usize {
+    if b == 0 {
+        a
+    } else {
+        gcd(b, a % b)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::NoteType;
+
+    fn rhythm(pattern: &str) -> Vec<bool> {
+        pattern.chars().map(|c| c == 'x').collect()
+    }
+
+    #[test]
+    fn euclidean_rhythms() {
+        assert_eq!(euclidean(3, 8, 0), rhythm("x..x..x."));
+        assert_eq!(euclidean(5, 8, 0), rhythm("x.xx.xx."));
+        assert_eq!(euclidean(5, 13, 0), rhythm("x..x.x..x.x.."));
+        assert_eq!(euclidean(0, 4, 0), rhythm("...."));
+        assert_eq!(euclidean(4, 4, 0), rhythm("xxxx"));
+    }
+
+    #[test]
+    fn euclidean_rotation() {
+        assert_eq!(euclidean(3, 8, 1), rhythm(".x..x..x"));
+        assert_eq!(euclidean(3, 8, 9), euclidean(3, 8, 1));
+    }
+
+    #[test]
+    fn polymeter_lines_up_at_the_lcm() {
+        let polymeter = Polymeter::new()
+            .with_cycle(Lane::euclidean(Note::new(NoteType::C, 2), 3, 4, 0))
+            .with_cycle(Lane::euclidean(Note::new(NoteType::D, 2), 2, 6, 0));
+        assert_eq!(polymeter.length(), 12);
+    }
+}
@@ -0,0 +1,492 @@
+//! A compact text format for melodies, compiled to a [`Sequence`].
+//!
+//! ```text
+//! t120 v100            # tempo in quarter notes per minute, velocity from 0 to 127
+//! C4/4 D4/8 Eb4/8.     # notes with durations (1 = whole, 4 = quarter, dots lengthen)
+//! [C4 E4 G4]/2 r/4     # chords and rests
+//! G4/4~ G4/8 F#4       # ties, and the duration carries over when left out
+//! |: C4/8 D4 E4 :|x3   # repeats, twice unless a count is given
+//! ```
+//! Bar lines (`|`) are allowed anywhere and ignored.
+
+use std::{error, fmt::Display};
+
+use crate::{Error, Note, NoteEvent, NoteType, Sequence};
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ParseError {
+    pub line: usize,
+    pub column: usize,
+    pub message: String,
+}
+
+impl Display for ParseError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}:{}: {}", self.line, self.column, self.message)
+    }
+}
+
+impl error::Error for ParseError {}
+
+impl From<ParseError> for Error {
+    fn from(err: ParseError) -> Self {
+        Self::Other(Box::new(err))
+    }
+}
+
+pub type ParseResult<T> = core::result::Result<T, ParseError>;
+
+/// Parses a score and times it into a sequence of notes
+pub fn parse(source: &str) -> ParseResult<Sequence> {
+    let mut parser = Parser {
+        source,
+        chars: source.char_indices().collect(),
+        position: 0,
+    };
+    let items = parser.items(false)?;
+
+    let mut state = State {
+        time: 0.0,
+        bpm: 120.0,
+        velocity: 100.0 / 127.0,
+        duration: 1.0,
+        tied: Vec::new(),
+        sequence: Sequence::new(),
+    };
+    state.run(&items);
+    Ok(state.sequence)
+}
+
+#[derive(Clone, Debug, PartialEq)]
+enum Item {
+    Tempo(f32),
+    Velocity(f32),
+    /// Rests have no notes. Durations are in quarter notes
+    Element {
+        notes: Vec<Note>,
+        duration: Option<f32>,
+        tie: bool,
+    },
+    Repeat(Vec<Item>, u32),
+}
+
+struct State {
+    time: f64,
+    bpm: f32,
+    velocity: f32,
+    duration: f32,
+    /// Events waiting to be extended by the next element
+    tied: Vec<usize>,
+    sequence: Sequence,
+}
+
+impl State {
+    fn run(&mut self, items: &[Item]) {
+        for item in items {
+            match item {
+                Item::Tempo(bpm) => self.bpm = *bpm,
+                Item::Velocity(velocity) => self.velocity = *velocity,
+                Item::Element {
+                    notes,
+                    duration,
+                    tie,
+                } => {
+                    self.duration = duration.unwrap_or(self.duration);
+                    let seconds = self.duration as f64 * 60.0 / self.bpm as f64;
+                    let tied = std::mem::take(&mut self.tied);
+                    for &note in notes {
+                        let events = self.sequence.events_mut();
+                        let index = match tied.iter().find(|&&i| events[i].note == note) {
+                            Some(&i) => {
+                                events[i].duration += seconds;
+                                i
+                            }
+                            None => {
+                                events.push(NoteEvent::new(
+                                    note,
+                                    self.time,
+                                    seconds,
+                                    self.velocity,
+                                ));
+                                events.len() - 1
+                            }
+                        };
+                        if *tie {
+                            self.tied.push(index);
+                        }
+                    }
+                    self.time += seconds;
+                }
+                Item::Repeat(body, times) => {
+                    for _ in 0..*times {
+                        self.run(body);
+                    }
+                }
+            }
+        }
+    }
+}
+
+struct Parser<'a> {
+    source: &'a str,
+    chars: Vec<(usize, char)>,
+    position: usize,
+}
+
+impl Parser<'_> {
+    fn peek(&self) -> Option<char> {
+        self.chars.get(self.position).map(|&(_, c)| c)
+    }
+
+    fn bump(&mut self) -> Option<char> {
+        let c = self.peek();
+        self.position += 1;
+        c
+    }
+
+    fn eat(&mut self, c: char) -> bool {
+        if self.peek() == Some(c) {
+            self.position += 1;
+            true
+        } else {
+            false
+        }
+    }
+
+    fn error_at<T>(&self, position: usize, message: impl Into<String>) -> ParseResult<T> {
+        let offset = self
+            .chars
+            .get(position)
+            .map_or(self.source.len(), |&(i, _)| i);
+        let before = &self.source[..offset];
+        let line = before.matches('\n').count() + 1;
+        let column = before.len() - before.rfind('\n').map_or(0, |i| i + 1) + 1;
+        Err(ParseError {
+            line,
+            column,
+            message: message.into(),
+        })
+    }
+
+    fn error<T>(&self, message: impl Into<String>) -> ParseResult<T> {
+        self.error_at(self.position, message)
+    }
+
+    /// Skips whitespace, bar lines and comments
+    fn skip(&mut self) {
+        while let Some(c) = self.peek() {
+            match c {
+                '#' => {
+                    while !matches!(self.peek(), Some('\n') | None) {
+                        self.position += 1;
+                    }
+                }
+                '|' if self.chars.get(self.position + 1).map(|&(_, c)| c) != Some(':') => {
+                    self.position += 1
+                }
+                c if c.is_whitespace() => self.position += 1,
+                _ => break,
+            }
+        }
+    }
+
+    fn number(&mut self, what: &str) -> ParseResult<u32> {
+        let start = self.position;
+        while self.peek().is_some_and(|c| c.is_ascii_digit()) {
+            self.position += 1;
+        }
+        if start == self.position {
+            return self.error(format!("Expected {}", what));
+        }
+        let digits: String = self.chars[start..self.position]
+            .iter()
+            .map(|&(_, c)| c)
+            .collect();
+        match digits.parse() {
+            Ok(n) => Ok(n),
+            Err(_) => self.error_at(start, format!("{} is too large", what)),
+        }
+    }
+
+    fn items(&mut self, in_repeat: bool) -> ParseResult<Vec<Item>> {
+        let mut items = Vec::new();
+        loop {
+            self.skip();
+            let start = self.position;
+            let item = match self.peek() {
+                None if in_repeat => return self.error("Unclosed repeat, expected ':|'"),
+                None => return Ok(items),
+                Some(':') if self.chars.get(self.position + 1).map(|&(_, c)| c) == Some('|') => {
+                    if in_repeat {
+                        return Ok(items);
+                    }
+                    return self.error("':|' without a matching '|:'");
+                }
+                Some('|') => {
+                    self.position += 2;
+                    let body = self.items(true)?;
+                    self.position += 2;
+                    let times = if self.eat('x') {
+                        self.number("a repeat count after 'x'")?
+                    } else {
+                        2
+                    };
+                    Item::Repeat(body, times)
+                }
+                Some('t') => {
+                    self.bump();
+                    let bpm = self.number("a tempo after 't'")?;
+                    if bpm == 0 {
+                        return self.error_at(start, "Tempo must be above 0");
+                    }
+                    Item::Tempo(bpm as f32)
+                }
+                Some('v') => {
+                    self.bump();
+                    let velocity = self.number("a velocity after 'v'")?;
+                    if velocity > 127 {
+                        return self.error_at(start, "Velocity must be between 0 and 127");
+                    }
+                    Item::Velocity(velocity as f32 / 127.0)
+                }
+                Some('r') => {
+                    self.bump();
+                    self.element(Vec::new())?
+                }
+                Some('[') => {
+                    self.bump();
+                    let mut notes = Vec::new();
+                    loop {
+                        self.skip();
+                        match self.peek() {
+                            Some(']') => break,
+                            None => return self.error_at(start, "Unclosed chord, expected ']'"),
+                            _ => notes.push(self.note()?),
+                        }
+                    }
+                    self.bump();
+                    if notes.is_empty() {
+                        return self.error_at(start, "Empty chord");
+                    }
+                    self.element(notes)?
+                }
+                Some('A'..='G') => {
+                    let note = self.note()?;
+                    self.element(vec![note])?
+                }
+                Some(c) => {
+                    return self.error(format!(
+                        "Unexpected '{}', expected a note, rest, chord, tempo, velocity or repeat",
+                        c
+                    ))
+                }
+            };
+            items.push(item);
+        }
+    }
+
+    /// Duration and tie following a note, chord or rest
+    fn element(&mut self, notes: Vec<Note>) -> ParseResult<Item> {
+        let duration = if self.eat('/') {
+            let start = self.position;
+            let division = self.number("a duration after '/'")?;
+            if !division.is_power_of_two() || division > 64 {
+                return self.error_at(
+                    start,
+                    format!(
+                        "Unknown duration {}, expected 1, 2, 4, 8, 16, 32 or 64",
+                        division
+                    ),
+                );
+            }
+            let mut quarters = 4.0 / division as f32;
+            let mut dot = quarters;
+            while self.eat('.') {
+                dot /= 2.0;
+                quarters += dot;
+            }
+            Some(quarters)
+        } else {
+            None
+        };
+        let tie = self.eat('~');
+        if tie && notes.is_empty() {
+            return self.error_at(self.position - 1, "Rests cannot be tied");
+        }
+        Ok(Item::Element {
+            notes,
+            duration,
+            tie,
+        })
+    }
+
+    fn note(&mut self) -> ParseResult<Note> {
+        let start = self.position;
+        let name = match self.bump() {
+            Some(c @ 'A'..='G') => c,
+            Some(c) => return self.error_at(start, format!("Expected a note name, found '{}'", c)),
+            None => return self.error_at(start, "Expected a note name"),
+        };
+        let mut semitone: i32 = match name {
+            'C' => 0,
+            'D' => 2,
+            'E' => 4,
+            'F' => 5,
+            'G' => 7,
+            'A' => 9,
+            _ => 11,
+        };
+        if self.eat('b') {
+            semitone -= 1;
+        } else if self.eat('#') {
+            semitone += 1;
+        }
+        if !self.peek().is_some_and(|c| c.is_ascii_digit()) {
+            return self.error(format!("Expected an octave after note name '{}'", name));
+        }
+        let octave = self.number("an octave")? as i32 + semitone.div_euclid(12);
+        if !(0..=8).contains(&octave) {
+            return self.error_at(start, "Octave must be between 0 and 8");
+        }
+        let note_type: NoteType = NoteType::in_frequency_order()[semitone.rem_euclid(12) as usize];
+        Ok(Note::new(note_type, octave as u32))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Eb4, A4, C4, D4, E4, G4};
+
+    fn timings(source: &str) -> Vec<(Note, f64, f64)> {
+        parse(source)
+            .unwrap()
+            .events()
+            .iter()
+            .map(|e| (e.note, e.start, e.duration))
+            .collect()
+    }
+
+    fn error(source: &str) -> (usize, usize, String) {
+        let err = parse(source).unwrap_err();
+        (err.line, err.column, err.message)
+    }
+
+    #[test]
+    fn durations_and_dots() {
+        assert_eq!(
+            timings("C4/4 D4/8 Eb4/8. E4"),
+            [
+                (C4, 0.0, 0.5),
+                (D4, 0.5, 0.25),
+                (Eb4, 0.75, 0.375),
+                (E4, 1.125, 0.375),
+            ]
+        );
+        assert_eq!(timings("t60 C4/1.."), [(C4, 0.0, 7.0)]);
+    }
+
+    #[test]
+    fn chords_rests_and_velocity() {
+        let sequence = parse("v127 [C4 E4 G4]/2 r/4 | v0 A4/4").unwrap();
+        let events = sequence.events();
+        assert_eq!(events.len(), 4);
+        assert!(events[..3]
+            .iter()
+            .all(|e| e.start == 0.0 && e.velocity == 1.0));
+        assert_eq!((events[3].note, events[3].start), (A4, 1.5));
+        assert_eq!(events[3].velocity, 0.0);
+    }
+
+    #[test]
+    fn accidentals_cross_octaves() {
+        assert_eq!(timings("B#3/4")[0].0, C4);
+        assert_eq!(timings("Cb5/4")[0].0, Note::new(NoteType::B, 4));
+    }
+
+    #[test]
+    fn ties_join_notes() {
+        assert_eq!(
+            timings("G4/4~ G4/8 C4"),
+            [(G4, 0.0, 0.75), (C4, 0.75, 0.25)]
+        );
+        // A tie to a different note is dropped
+        assert_eq!(timings("G4/4~ C4"), [(G4, 0.0, 0.5), (C4, 0.5, 0.5)]);
+        assert_eq!(
+            timings("[C4 E4]/4~ [C4 G4]/4"),
+            [(C4, 0.0, 1.0), (E4, 0.0, 0.5), (G4, 0.5, 0.5)]
+        );
+    }
+
+    #[test]
+    fn repeats() {
+        assert_eq!(parse("|: C4/8 D4 :|").unwrap().events().len(), 4);
+        let sequence = parse("|: C4/8 |: D4 :|x3 :|x2 # nested").unwrap();
+        assert_eq!(sequence.events().len(), 8);
+        assert_eq!(sequence.duration(), 2.0);
+    }
+
+    #[test]
+    fn comments_and_bar_lines_are_ignored() {
+        assert_eq!(
+            timings("# intro\nC4/4 | D4 |\n# end"),
+            [(C4, 0.0, 0.5), (D4, 0.5, 0.5)]
+        );
+    }
+
+    #[test]
+    fn error_positions() {
+        assert_eq!(
+            error("C4/4 |: D4"),
+            (1, 11, "Unclosed repeat, expected ':|'".into())
+        );
+        assert_eq!(
+            error("C4 :|"),
+            (1, 4, "':|' without a matching '|:'".into())
+        );
+        assert_eq!(
+            error("C4/4\n  C9/4"),
+            (2, 3, "Octave must be between 0 and 8".into())
+        );
+        assert_eq!(
+            error("Cb0"),
+            (1, 1, "Octave must be between 0 and 8".into())
+        );
+        assert_eq!(
+            error("C4/3"),
+            (
+                1,
+                4,
+                "Unknown duration 3, expected 1, 2, 4, 8, 16, 32 or 64".into()
+            )
+        );
+        assert_eq!(error("r/4~"), (1, 4, "Rests cannot be tied".into()));
+        assert_eq!(
+            error("[C4 E4"),
+            (1, 1, "Unclosed chord, expected ']'".into())
+        );
+        assert_eq!(
+            error("C/4"),
+            (1, 2, "Expected an octave after note name 'C'".into())
+        );
+        assert_eq!(error("t0"), (1, 1, "Tempo must be above 0".into()));
+        assert_eq!(
+            error("v128"),
+            (1, 1, "Velocity must be between 0 and 127".into())
+        );
+        assert_eq!(
+            error("C4 x"),
+            (
+                1,
+                4,
+                "Unexpected 'x', expected a note, rest, chord, tempo, velocity or repeat".into()
+            )
+        );
+    }
+
+    #[test]
+    fn errors_display_their_position() {
+        let err = parse("C4\nr~").unwrap_err();
+        assert_eq!(err.to_string(), "2:2: Rests cannot be tied");
+    }
+}
@@ -0,0 +1,141 @@
+use crate::{Instrument, Note, SampleType, Samples, Voice, VoiceEvent, WavAudio};
+
+/// A note placed on a timeline. Times are in seconds, kept in double precision so that long
+/// pieces stay sample accurate. Velocity goes from 0 to 1
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct NoteEvent {
+    pub note: Note,
+    pub start: f64,
+    pub duration: f64,
+    pub velocity: f32,
+}
+
+impl NoteEvent {
+    pub fn new(note: Note, start: f64, duration: f64, velocity: f32) -> NoteEvent {
+        NoteEvent {
+            note,
+            start,
+            duration,
+            velocity,
+        }
+    }
+
+    pub fn end(&self) -> f64 {
+        self.start + self.duration
+    }
+}
+
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Sequence {
+    events: Vec<NoteEvent>,
+}
+
+impl Sequence {
+    /// Longest a voice may ring on after its note has ended
+    pub const MAX_TAIL: f32 = 10.0;
+
+    pub fn new() -> Sequence {
+        Sequence { events: Vec::new() }
+    }
+
+    pub fn from_events(events: Vec<NoteEvent>) -> Sequence {
+        Sequence { events }
+    }
+
+    pub fn add(&mut self, event: NoteEvent) {
+        self.events.push(event);
+    }
+
+    pub fn add_note(&mut self, note: Note, start: f64, duration: f64, velocity: f32) {
+        self.add(NoteEvent::new(note, start, duration, velocity));
+    }
+
+    pub fn events(&self) -> &[NoteEvent] {
+        &self.events
+    }
+
+    pub fn events_mut(&mut self) -> &mut Vec<NoteEvent> {
+        &mut self.events
+    }
+
+    /// End of the last note
+    pub fn duration(&self) -> f64 {
+        self.events.iter().map(NoteEvent::end).fold(0.0, f64::max)
+    }
+
+    /// Moves every event `offset` seconds later
+    pub fn shift(&mut self, offset: f64) {
+        for event in &mut self.events {
+            event.start += offset;
+        }
+    }
+
+    /// Places all events of `other` after the end of this sequence
+    pub fn append(&mut self, mut other: Sequence) {
+        other.shift(self.duration());
+        self.events.extend(other.events);
+    }
+
+    pub fn merge(&mut self, other: Sequence) {
+        self.events.extend(other.events);
+    }
+
+    pub fn sort(&mut self) {
+        self.events.sort_by(|a, b| a.start.total_cmp(&b.start));
+    }
+
+    /// Note on and note off events at their sample index, sorted, for driving a
+    /// [`VoiceManager`](crate::VoiceManager) with bounded polyphony
+    pub fn voice_events(&self, sample_rate: u32) -> Vec<(usize, VoiceEvent)> {
+        let mut events: Vec<(usize, VoiceEvent)> = self
+            .events
+            .iter()
+            .flat_map(|event| {
+                let (start, end) = sample_range(event, sample_rate);
+                [
+                    (start, VoiceEvent::NoteOn(event.note, event.velocity)),
+                    (end, VoiceEvent::NoteOff(event.note)),
+                ]
+            })
+            .collect();
+        // At the same index, notes are released before new ones start
+        events.sort_by_key(|&(index, event)| (index, matches!(event, VoiceEvent::NoteOn(..))));
+        events
+    }
+
+    /// Plays every event on its own voice of `instrument`, so overlapping notes never cut each other off.
+    /// The result lasts until the last voice has died away
+    pub fn render<I: Instrument>(&self, instrument: &I, sample_rate: u32) -> Samples {
+        let mut points: Vec<f32> = Vec::new();
+        let max_tail = (Self::MAX_TAIL * sample_rate as f32) as usize;
+
+        for event in &self.events {
+            let (start, end) = sample_range(event, sample_rate);
+            let mut voice = instrument.voice(event.note, event.velocity, sample_rate);
+            let mut index = start;
+            while index < end + max_tail && !voice.is_finished() {
+                if index == end {
+                    voice.release();
+                }
+                if index >= points.len() {
+                    points.resize(index + 1, 0.0);
+                }
+                points[index] += voice.next_sample();
+                index += 1;
+            }
+        }
+
+        Samples::new(SampleType::Pointsf32(points)).with_sample_rate(sample_rate)
+    }
+
+    pub fn render_wav<I: Instrument>(&self, instrument: &I, sample_rate: u32) -> WavAudio {
+        WavAudio::mono(self.render(instrument, sample_rate))
+    }
+}
+
+fn sample_range(event: &NoteEvent, sample_rate: u32) -> (usize, usize) {
+    let rate = sample_rate as f64;
+    let start = (event.start.max(0.0) * rate).round() as usize;
+    let end = (event.end().max(0.0) * rate).round() as usize;
+    (start, end.max(start))
+}
@@ -0,0 +1,167 @@
+use std::f32::consts::TAU;
+
+use crate::{Note, SampleType, Samples, SineWave, Wave};
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Direction {
+    Up,
+    Down,
+}
+
+/// Octave spaced partials under a fixed bell shaped spectral envelope. Moving the partials while
+/// the envelope stays put gives a tone that seems to rise or fall forever
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Shepard {
+    base: f32,
+    pub octaves: u32,
+    /// Octaves per second for the continuous glissando
+    pub speed: f32,
+    pub direction: Direction,
+    /// Standard deviation of the bell, in octaves
+    pub width: f32,
+    pub amplitude: f32,
+}
+
+impl Shepard {
+    pub fn new(note: Note) -> Shepard {
+        Shepard::with_frequency(note.frequency())
+    }
+
+    /// `base` is the frequency of the lowest partial at position 0
+    pub fn with_frequency(base: f32) -> Shepard {
+        Shepard {
+            base,
+            octaves: 8,
+            speed: 0.1,
+            direction: Direction::Up,
+            width: 1.5,
+            amplitude: 1.0,
+        }
+    }
+
+    pub fn with_octaves(mut self, octaves: u32) -> Shepard {
+        self.octaves = octaves.max(1);
+        self
+    }
+
+    pub fn with_speed(mut self, speed: f32) -> Shepard {
+        self.speed = speed;
+        self
+    }
+
+    pub fn with_direction(mut self, direction: Direction) -> Shepard {
+        self.direction = direction;
+        self
+    }
+
+    pub fn with_width(mut self, width: f32) -> Shepard {
+        self.width = width;
+        self
+    }
+
+    /// # Panics
+    /// Panics if amplitude is not between 0 and 1
+    #[track_caller]
+    pub fn with_amplitude(mut self, amplitude: f32) -> Shepard {
+        assert!((0.0..=1.0).contains(&amplitude), "Invalid amplitude");
+        self.amplitude = amplitude;
+        self
+    }
+
+    pub fn base(&self) -> f32 {
+        self.base
+    }
+
+    /// Level of a partial `octave` octaves above the base, with the peak in the middle of the range
+    pub fn envelope(&self, octave: f32) -> f32 {
+        let center = self.octaves as f32 / 2.0;
+        (-(octave - center).powi(2) / (2.0 * self.width * self.width)).exp()
+    }
+
+    /// Octave of every partial above the base when the tone has moved `position` octaves
+    fn partials(&self, position: f32) -> impl Iterator<Item = f32> + '_ {
+        let octaves = self.octaves as f32;
+        (0..self.octaves).map(move |i| (i as f32 + position).rem_euclid(octaves))
+    }
+
+    fn normalization(&self) -> f32 {
+        let loudest = (0..16)
+            .map(|step| {
+                self.partials(step as f32 / 16.0)
+                    .map(|o| self.envelope(o))
+                    .sum::<f32>()
+            })
+            .fold(0.0, f32::max);
+        self.amplitude / loudest.max(f32::EPSILON)
+    }
+
+    /// A single static Shepard tone, `position` octaves above the base (only its fractional part matters)
+    pub fn wave(&self, position: f32) -> Wave {
+        let scale = self.normalization();
+        Wave::new(
+            self.partials(position)
+                .map(|octave| {
+                    SineWave::with_frequency(self.base * 2f32.powf(octave))
+                        * (scale * self.envelope(octave))
+                })
+                .collect(),
+        )
+    }
+
+    /// Shepard scale: `steps` static tones per octave, each held for `step` seconds
+    pub fn render_scale(&self, sample_rate: u32, steps: u32, step: f32, seconds: f32) -> Samples {
+        let rate = sample_rate as f32;
+        let count = (rate * seconds) as usize;
+        let step_len = ((rate * step) as usize).max(1);
+        let fade = (rate * 0.005).min(step_len as f32 / 2.0).max(1.0);
+        let sign = match self.direction {
+            Direction::Up => 1.0,
+            Direction::Down => -1.0,
+        };
+
+        let mut wave = self.wave(0.0);
+        let points = (0..count)
+            .map(|i| {
+                let index = i / step_len;
+                let within = (i % step_len) as f32;
+                if i % step_len == 0 {
+                    wave = self.wave(sign * index as f32 / steps.max(1) as f32);
+                }
+                // Short fades at both ends of every step keep the changes from clicking
+                let gain = (within / fade)
+                    .min((step_len as f32 - within) / fade)
+                    .min(1.0);
+                gain * wave.at(i as f32 / rate)
+            })
+            .collect();
+        Samples::new(SampleType::Pointsf32(points)).with_sample_rate(sample_rate)
+    }
+
+    /// Risset glissando: every partial glides continuously and wraps around at the top or bottom
+    pub fn render(&self, sample_rate: u32, seconds: f32) -> Samples {
+        let rate = sample_rate as f32;
+        let count = (rate * seconds) as usize;
+        let scale = self.normalization();
+        let velocity = match self.direction {
+            Direction::Up => self.speed,
+            Direction::Down => -self.speed,
+        };
+
+        let mut phases = vec![0.0f32; self.octaves as usize];
+        let points = (0..count)
+            .map(|i| {
+                let position = velocity * i as f32 / rate;
+                self.partials(position)
+                    .zip(phases.iter_mut())
+                    .map(|(octave, phase)| {
+                        let value = self.envelope(octave) * phase.sin();
+                        *phase = (*phase + TAU * self.base * 2f32.powf(octave) / rate) % TAU;
+                        value
+                    })
+                    .sum::<f32>()
+                    * scale
+            })
+            .collect();
+        Samples::new(SampleType::Pointsf32(points)).with_sample_rate(sample_rate)
+    }
+}
@@ -0,0 +1,250 @@
+use std::f64::consts::TAU;
+
+use crate::{Note, SampleType, Samples};
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum SweepKind {
+    /// Frequency changes by the same number of Hz every second
+    Linear,
+    /// Frequency changes by the same number of octaves every second
+    Exponential,
+}
+
+impl SweepKind {
+    /// Frequency `progress` (from 0 to 1) of the way from `start` to `end`
+    pub fn interpolate(&self, start: f32, end: f32, progress: f32) -> f32 {
+        match self {
+            SweepKind::Linear => start + (end - start) * progress,
+            SweepKind::Exponential => start * (end / start).powf(progress),
+        }
+    }
+}
+
+/// A sine whose frequency glides from `start` to `end` over `duration` seconds and then holds.
+/// A `duration` of 0 or less jumps straight to `end`
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Sweep {
+    pub start: f32,
+    pub end: f32,
+    pub duration: f32,
+    pub kind: SweepKind,
+    pub amplitude: f32,
+}
+
+impl Sweep {
+    /// # Panics
+    /// Panics if an exponential sweep starts or ends at 0 Hz or below
+    #[track_caller]
+    pub fn new(start: f32, end: f32, duration: f32, kind: SweepKind) -> Sweep {
+        assert!(
+            kind == SweepKind::Linear || (start > 0.0 && end > 0.0),
+            "Exponential sweeps need positive frequencies"
+        );
+        Sweep {
+            start,
+            end,
+            duration,
+            kind,
+            amplitude: 1.0,
+        }
+    }
+
+    pub fn linear(start: f32, end: f32, duration: f32) -> Sweep {
+        Sweep::new(start, end, duration, SweepKind::Linear)
+    }
+
+    /// Exponential (logarithmic) sine sweep, as used for impulse response measurement
+    /// # Panics
+    /// Panics if `start` or `end` is not positive
+    #[track_caller]
+    pub fn exponential(start: f32, end: f32, duration: f32) -> Sweep {
+        Sweep::new(start, end, duration, SweepKind::Exponential)
+    }
+
+    pub fn between_notes(from: Note, to: Note, duration: f32, kind: SweepKind) -> Sweep {
+        Sweep::new(from.frequency(), to.frequency(), duration, kind)
+    }
+
+    pub fn with_amplitude(mut self, amplitude: f32) -> Sweep {
+        self.amplitude = amplitude;
+        self
+    }
+
+    pub fn frequency_at(&self, x: f32) -> f32 {
+        self.frequency_at_time(x as f64) as f32
+    }
+
+    fn frequency_at_time(&self, x: f64) -> f64 {
+        let (f0, f1, length) = (self.start as f64, self.end as f64, self.duration as f64);
+        if x >= length || length <= 0.0 {
+            return f1;
+        }
+        let progress = x.max(0.0) / length;
+        match self.kind {
+            SweepKind::Linear => f0 + (f1 - f0) * progress,
+            SweepKind::Exponential => f0 * (f1 / f0).powf(progress),
+        }
+    }
+
+    /// Exact integral of the frequency, in radians
+    pub fn phase_at(&self, x: f32) -> f64 {
+        self.phase_at_time(x as f64)
+    }
+
+    /// Phase at sample `i`, with the time kept in double precision so long sweeps stay exact
+    pub fn phase_at_sample(&self, i: usize, sample_rate: u32) -> f64 {
+        self.phase_at_time(i as f64 / sample_rate as f64)
+    }
+
+    fn phase_at_time(&self, x: f64) -> f64 {
+        let (f0, f1, length) = (self.start as f64, self.end as f64, self.duration as f64);
+        let x = x.max(0.0);
+        if length <= 0.0 {
+            // No glide: the sweep is at its end frequency from the start
+            return TAU * f1 * x;
+        }
+        let t = x.min(length);
+        let sweep = match self.kind {
+            SweepKind::Linear => f0 * t + (f1 - f0) * t * t / (2.0 * length),
+            SweepKind::Exponential if f0 == f1 => f0 * t,
+            SweepKind::Exponential => {
+                let k = (f1 / f0).ln() / length;
+                f0 * ((k * t).exp() - 1.0) / k
+            }
+        };
+        TAU * (sweep + f1 * (x - t))
+    }
+
+    pub fn at(&self, x: f32) -> f32 {
+        self.amplitude * (self.phase_at(x) % TAU).sin() as f32
+    }
+
+    pub fn render(&self, sample_rate: u32, seconds: f32) -> Samples {
+        let count = (sample_rate as f32 * seconds) as usize;
+        let points = (0..count)
+            .map(|i| self.amplitude * (self.phase_at_sample(i, sample_rate) % TAU).sin() as f32)
+            .collect();
+        Samples::new(SampleType::Pointsf32(points)).with_sample_rate(sample_rate)
+    }
+
+    /// Farina's inverse filter for an exponential sweep: the sweep reversed in time and tilted by
+    /// -6dB per octave. Convolving a recorded sweep with it gives the impulse response
+    pub fn inverse_filter(&self, sample_rate: u32) -> Samples {
+        let duration = self.duration as f64;
+        let count = (sample_rate as f64 * duration) as usize;
+        let rate = (self.end as f64 / self.start as f64).ln() / duration;
+        let points = (0..count)
+            .map(|i| {
+                let x = i as f64 / sample_rate as f64;
+                let phase = self.phase_at_time(duration - x) % TAU;
+                (self.amplitude as f64 * phase.sin() * (-x * rate).exp()) as f32
+            })
+            .collect();
+        Samples::new(SampleType::Pointsf32(points)).with_sample_rate(sample_rate)
+    }
+}
+
+/// A monophonic line that glides from each note into the next
+#[derive(Clone, Debug, PartialEq)]
+pub struct Portamento {
+    notes: Vec<(Note, f32)>,
+    /// Length of each glide in seconds, taken from the start of the next note
+    pub glide: f32,
+    pub kind: SweepKind,
+    pub amplitude: f32,
+}
+
+impl Portamento {
+    pub fn new(glide: f32) -> Portamento {
+        Portamento {
+            notes: Vec::new(),
+            glide,
+            kind: SweepKind::Exponential,
+            amplitude: 1.0,
+        }
+    }
+
+    pub fn with_kind(mut self, kind: SweepKind) -> Portamento {
+        self.kind = kind;
+        self
+    }
+
+    pub fn with_amplitude(mut self, amplitude: f32) -> Portamento {
+        self.amplitude = amplitude;
+        self
+    }
+
+    /// Appends `note`, held for `duration` seconds
+    pub fn then(mut self, note: Note, duration: f32) -> Portamento {
+        self.notes.push((note, duration));
+        self
+    }
+
+    pub fn notes(&self) -> &[(Note, f32)] {
+        &self.notes
+    }
+
+    pub fn duration(&self) -> f32 {
+        self.notes.iter().map(|(_, d)| d).sum()
+    }
+
+    pub fn frequency_at(&self, x: f32) -> f32 {
+        self.frequency_at_time(x as f64) as f32
+    }
+
+    fn frequency_at_time(&self, x: f64) -> f64 {
+        let mut start = 0.0;
+        let mut previous: Option<Note> = None;
+        for (i, &(note, duration)) in self.notes.iter().enumerate() {
+            if x < start + duration as f64 || i == self.notes.len() - 1 {
+                return match previous {
+                    Some(from) => Sweep::between_notes(from, note, self.glide, self.kind)
+                        .frequency_at_time(x - start),
+                    None => note.frequency() as f64,
+                };
+            }
+            start += duration as f64;
+            previous = Some(note);
+        }
+        0.0
+    }
+
+    /// Renders every note, keeping the phase continuous through the glides
+    pub fn render(&self, sample_rate: u32) -> Samples {
+        let count = (sample_rate as f64 * self.duration() as f64) as usize;
+        let mut phase = 0.0f64;
+        let points = (0..count)
+            .map(|i| {
+                let value = self.amplitude * phase.sin() as f32;
+                let frequency = self.frequency_at_time(i as f64 / sample_rate as f64);
+                phase = (phase + TAU * frequency / sample_rate as f64) % TAU;
+                value
+            })
+            .collect();
+        Samples::new(SampleType::Pointsf32(points)).with_sample_rate(sample_rate)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Kick;
+
+    #[test]
+    fn zero_length_sweeps_hold_the_end() {
+        for sweep in [
+            Sweep::linear(100.0, 200.0, 0.0),
+            Sweep::exponential(100.0, 200.0, 0.0),
+        ] {
+            assert_eq!(sweep.frequency_at(0.0), 200.0);
+            assert!((sweep.phase_at(0.1) - TAU * 20.0).abs() < 1e-4);
+            assert!(sweep.at(0.1).is_finite());
+        }
+        let kick = Kick {
+            pitch_decay: 0.0,
+            ..Kick::default()
+        }
+        .render(8000);
+        assert!((0..kick.sample_count().unwrap()).all(|i| kick.sample_f32(i).unwrap().is_finite()));
+    }
+}
@@ -0,0 +1,392 @@
+use std::fmt::Display;
+
+use crate::{Note, NoteEvent};
+
+/// A position in bars, beats and ticks. Bars and beats count from 1, ticks from 0
+#[derive(Copy, Clone, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Position {
+    pub bar: u32,
+    pub beat: u32,
+    pub tick: u32,
+}
+
+impl Position {
+    pub const fn new(bar: u32, beat: u32, tick: u32) -> Position {
+        Position { bar, beat, tick }
+    }
+}
+
+impl Display for Position {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}:{}:{}", self.bar, self.beat, self.tick)
+    }
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct TimeSignature {
+    pub beats: u32,
+    /// Note value of one beat, 4 for quarter notes, 8 for eighths
+    pub unit: u32,
+}
+
+impl TimeSignature {
+    /// # Panics
+    /// Panics if either part is 0
+    #[track_caller]
+    pub const fn new(beats: u32, unit: u32) -> TimeSignature {
+        assert!(beats > 0 && unit > 0, "Invalid time signature");
+        TimeSignature { beats, unit }
+    }
+
+    /// Length of one beat in quarter notes
+    pub fn beat_length(&self) -> f64 {
+        4.0 / self.unit as f64
+    }
+
+    /// Length of one bar in quarter notes
+    pub fn bar_length(&self) -> f64 {
+        self.beats as f64 * self.beat_length()
+    }
+}
+
+impl Default for TimeSignature {
+    fn default() -> TimeSignature {
+        TimeSignature::new(4, 4)
+    }
+}
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+struct TempoChange {
+    /// In quarter notes from the start
+    at: f64,
+    bpm: f64,
+    /// Tempo is ramped linearly (in quarter notes) up to the next change
+    ramp: bool,
+}
+
+/// Tempo and meter over a piece. Tempos are in quarter notes per minute
+#[derive(Clone, Debug, PartialEq)]
+pub struct TempoMap {
+    tempos: Vec<TempoChange>,
+    /// Bar at which each signature starts, counting from 1
+    signatures: Vec<(u32, TimeSignature)>,
+    ticks_per_beat: u32,
+}
+
+impl TempoMap {
+    pub const DEFAULT_TICKS_PER_BEAT: u32 = 480;
+
+    /// # Panics
+    /// Panics if `bpm` is not positive
+    #[track_caller]
+    pub fn new(bpm: f64) -> TempoMap {
+        assert!(bpm > 0.0, "Tempo must be positive");
+        TempoMap {
+            tempos: vec![TempoChange {
+                at: 0.0,
+                bpm,
+                ramp: false,
+            }],
+            signatures: vec![(1, TimeSignature::default())],
+            ticks_per_beat: Self::DEFAULT_TICKS_PER_BEAT,
+        }
+    }
+
+    pub fn with_signature(mut self, signature: TimeSignature) -> TempoMap {
+        self.signatures[0].1 = signature;
+        self
+    }
+
+    pub fn with_ticks_per_beat(mut self, ticks_per_beat: u32) -> TempoMap {
+        self.ticks_per_beat = ticks_per_beat.max(1);
+        self
+    }
+
+    pub fn ticks_per_beat(&self) -> u32 {
+        self.ticks_per_beat
+    }
+
+    /// Jumps to `bpm` at `position`
+    /// # Panics
+    /// Panics if `bpm` is not positive
+    #[track_caller]
+    pub fn set_tempo(&mut self, position: Position, bpm: f64) {
+        assert!(bpm > 0.0, "Tempo must be positive");
+        let at = self.quarters(position);
+        self.insert_tempo(TempoChange {
+            at,
+            bpm,
+            ramp: false,
+        });
+    }
+
+    /// Changes the tempo gradually from whatever it is at `from` to `bpm` at `to`,
+    /// for an accelerando or ritardando
+    /// # Panics
+    /// Panics if `bpm` is not positive
+    #[track_caller]
+    pub fn ramp_tempo(&mut self, from: Position, to: Position, bpm: f64) {
+        assert!(bpm > 0.0, "Tempo must be positive");
+        let (start, end) = (self.quarters(from), self.quarters(to));
+        let current = self.bpm_at_quarters(start);
+        self.tempos.retain(|t| t.at < start || t.at > end);
+        self.insert_tempo(TempoChange {
+            at: start,
+            bpm: current,
+            ramp: true,
+        });
+        self.insert_tempo(TempoChange {
+            at: end,
+            bpm,
+            ramp: false,
+        });
+    }
+
+    /// Changes the meter from the start of `bar` onwards
+    pub fn set_signature(&mut self, bar: u32, signature: TimeSignature) {
+        let bar = bar.max(1);
+        // Tempo changes are stored in quarter notes, so keep them at the same bar position
+        let positions: Vec<Position> = self.tempos.iter().map(|t| self.position(t.at)).collect();
+        self.signatures.retain(|&(b, _)| b != bar);
+        self.signatures.push((bar, signature));
+        self.signatures.sort_by_key(|&(b, _)| b);
+        for (tempo, position) in self.tempos.iter_mut().zip(positions).skip(1) {
+            tempo.at = quarters_for(&self.signatures, self.ticks_per_beat, position);
+        }
+    }
+
+    pub fn signature_at(&self, bar: u32) -> TimeSignature {
+        self.signatures
+            .iter()
+            .rev()
+            .find(|&&(b, _)| b <= bar)
+            .map(|&(_, s)| s)
+            .unwrap_or_default()
+    }
+
+    pub fn bpm_at(&self, position: Position) -> f64 {
+        self.bpm_at_quarters(self.quarters(position))
+    }
+
+    /// Quarter notes from the start of the piece to `position`
+    pub fn quarters(&self, position: Position) -> f64 {
+        quarters_for(&self.signatures, self.ticks_per_beat, position)
+    }
+
+    /// Inverse of [`TempoMap::quarters`], rounding down to a whole tick
+    pub fn position(&self, quarters: f64) -> Position {
+        let mut start = 0.0;
+        for (i, &(bar, signature)) in self.signatures.iter().enumerate() {
+            let bars = match self.signatures.get(i + 1) {
+                Some(&(next, _)) => (next - bar) as f64,
+                None => f64::INFINITY,
+            };
+            let length = bars * signature.bar_length();
+            if quarters < start + length {
+                let within = quarters - start;
+                let whole_bars = (within / signature.bar_length()).floor();
+                let beats =
+                    (within - whole_bars * signature.bar_length()) / signature.beat_length();
+                let ticks = (beats.fract() * self.ticks_per_beat as f64 + 1e-6).floor();
+                return Position::new(
+                    bar + whole_bars as u32,
+                    beats.floor() as u32 + 1,
+                    ticks as u32,
+                );
+            }
+            start += length;
+        }
+        Position::new(1, 1, 0)
+    }
+
+    /// Seconds from the start of the piece to `position`
+    pub fn seconds(&self, position: Position) -> f64 {
+        self.seconds_at_quarters(self.quarters(position))
+    }
+
+    /// Sample index of `position` at `sample_rate`
+    pub fn sample(&self, position: Position, sample_rate: u32) -> usize {
+        (self.seconds(position) * sample_rate as f64).round() as usize
+    }
+
+    /// A note held from `start` until `end`, timed in seconds for a [`Sequence`](crate::Sequence)
+    pub fn note_event(
+        &self,
+        note: Note,
+        start: Position,
+        end: Position,
+        velocity: f32,
+    ) -> NoteEvent {
+        let start = self.seconds(start);
+        let end = self.seconds(end);
+        NoteEvent::new(note, start, end - start, velocity)
+    }
+
+    /// Seconds from the start of the piece to a point `quarters` quarter notes in
+    pub fn seconds_at_quarters(&self, quarters: f64) -> f64 {
+        let mut seconds = 0.0;
+        for (i, tempo) in self.tempos.iter().enumerate() {
+            if quarters <= tempo.at {
+                break;
+            }
+            let next = self.tempos.get(i + 1);
+            let end = next.map_or(quarters, |n| n.at.min(quarters));
+            let length = end - tempo.at;
+            seconds += match next {
+                Some(next) if tempo.ramp && next.bpm != tempo.bpm => {
+                    // Tempo is linear in quarters, so time is the integral of 60 / bpm
+                    let slope = (next.bpm - tempo.bpm) / (next.at - tempo.at);
+                    60.0 / slope * ((tempo.bpm + slope * length) / tempo.bpm).ln()
+                }
+                _ => 60.0 * length / tempo.bpm,
+            };
+        }
+        seconds
+    }
+
+    /// Inverse of [`TempoMap::seconds_at_quarters`]
+    pub fn quarters_at_seconds(&self, mut seconds: f64) -> f64 {
+        for (i, tempo) in self.tempos.iter().enumerate() {
+            let next = self.tempos.get(i + 1);
+            let length = match next {
+                Some(next) => {
+                    self.seconds_at_quarters(next.at) - self.seconds_at_quarters(tempo.at)
+                }
+                None => f64::INFINITY,
+            };
+            if seconds < length || next.is_none() {
+                return tempo.at
+                    + match next {
+                        Some(next) if tempo.ramp && next.bpm != tempo.bpm => {
+                            let slope = (next.bpm - tempo.bpm) / (next.at - tempo.at);
+                            tempo.bpm * ((seconds * slope / 60.0).exp() - 1.0) / slope
+                        }
+                        _ => seconds * tempo.bpm / 60.0,
+                    };
+            }
+            seconds -= length;
+        }
+        0.0
+    }
+
+    fn bpm_at_quarters(&self, quarters: f64) -> f64 {
+        let i = self
+            .tempos
+            .iter()
+            .rposition(|t| t.at <= quarters)
+            .unwrap_or_default();
+        let tempo = self.tempos[i];
+        match self.tempos.get(i + 1) {
+            Some(next) if tempo.ramp => {
+                tempo.bpm + (next.bpm - tempo.bpm) * (quarters - tempo.at) / (next.at - tempo.at)
+            }
+            _ => tempo.bpm,
+        }
+    }
+
+    fn insert_tempo(&mut self, change: TempoChange) {
+        self.tempos.retain(|t| t.at != change.at);
+        let i = self.tempos.partition_point(|t| t.at < change.at);
+        self.tempos.insert(i, change);
+    }
+}
+
+fn quarters_for(
+    signatures: &[(u32, TimeSignature)],
+    ticks_per_beat: u32,
+    position: Position,
+) -> f64 {
+    let bar = position.bar.max(1);
+    let mut quarters = 0.0;
+    for (i, &(start, signature)) in signatures.iter().enumerate() {
+        if start > bar {
+            break;
+        }
+        let end = signatures
+            .get(i + 1)
+            .map_or(bar, |&(next, _)| next.min(bar));
+        quarters += (end - start) as f64 * signature.bar_length();
+    }
+    let signature = signatures
+        .iter()
+        .rev()
+        .find(|&&(b, _)| b <= bar)
+        .map(|&(_, s)| s)
+        .unwrap_or_default();
+    let beats = position.beat.max(1) as f64 - 1.0 + position.tick as f64 / ticks_per_beat as f64;
+    quarters + beats * signature.beat_length()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn close(a: f64, b: f64) -> bool {
+        (a - b).abs() < 1e-9
+    }
+
+    #[test]
+    #[should_panic(expected = "Tempo must be positive")]
+    fn zero_tempo_is_rejected() {
+        TempoMap::new(0.0);
+    }
+
+    #[test]
+    #[should_panic(expected = "Tempo must be positive")]
+    fn negative_ramp_is_rejected() {
+        let mut tempo = TempoMap::new(120.0);
+        tempo.ramp_tempo(Position::new(1, 1, 0), Position::new(2, 1, 0), -60.0);
+    }
+
+    #[test]
+    fn constant_tempo() {
+        let tempo = TempoMap::new(120.0);
+        assert!(close(tempo.seconds(Position::new(2, 1, 0)), 2.0));
+        assert!(close(tempo.seconds(Position::new(1, 2, 240)), 0.75));
+        assert_eq!(tempo.sample(Position::new(3, 1, 0), 44100), 176400);
+    }
+
+    #[test]
+    fn ramp_is_integrated() {
+        let mut tempo = TempoMap::new(60.0);
+        tempo.ramp_tempo(Position::new(2, 1, 0), Position::new(3, 1, 0), 120.0);
+        // 4 quarters at 60, then bpm rising linearly over 4 quarters: 240 / 60 * ln 2
+        let ramp = 4.0 * 60.0 / 60.0 * 2f64.ln();
+        assert!(close(tempo.seconds(Position::new(3, 1, 0)), 4.0 + ramp));
+        assert!(close(
+            tempo.seconds(Position::new(4, 1, 0)),
+            4.0 + ramp + 2.0
+        ));
+        assert!(close(tempo.bpm_at(Position::new(2, 3, 0)), 90.0));
+    }
+
+    #[test]
+    fn seconds_and_quarters_round_trip() {
+        let mut tempo = TempoMap::new(100.0);
+        tempo.set_signature(3, TimeSignature::new(7, 8));
+        tempo.ramp_tempo(Position::new(2, 3, 0), Position::new(5, 2, 0), 155.0);
+        tempo.set_tempo(Position::new(7, 1, 0), 80.0);
+        for i in 0..200 {
+            let quarters = i as f64 * 0.173;
+            let seconds = tempo.seconds_at_quarters(quarters);
+            assert!(
+                close(tempo.quarters_at_seconds(seconds), quarters),
+                "{}",
+                quarters
+            );
+        }
+    }
+
+    #[test]
+    fn meter_change_moves_bars() {
+        let mut tempo = TempoMap::new(120.0);
+        tempo.set_tempo(Position::new(3, 1, 0), 60.0);
+        tempo.set_signature(2, TimeSignature::new(6, 8));
+        // Bar 2 is now 3 quarters long, and the tempo change stays at the start of bar 3
+        assert!(close(tempo.quarters(Position::new(3, 1, 0)), 7.0));
+        assert_eq!(tempo.bpm_at(Position::new(3, 1, 0)), 60.0);
+        assert!(close(tempo.seconds(Position::new(4, 1, 0)), 3.5 + 3.0));
+        assert_eq!(tempo.position(5.5), Position::new(2, 4, 0));
+        assert_eq!(tempo.position(8.25), Position::new(3, 3, 240));
+    }
+}
@@ -0,0 +1,152 @@
+use crate::{Note, SineWave, Wave};
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Partial {
+    pub ratio: f32,
+    pub amplitude: f32,
+    pub phase: f32,
+}
+
+impl Partial {
+    pub fn harmonic(n: u32, amplitude: f32) -> Partial {
+        Partial {
+            ratio: n as f32,
+            amplitude,
+            phase: 0.0,
+        }
+    }
+
+    pub fn inharmonic(ratio: f32, amplitude: f32) -> Partial {
+        Partial {
+            ratio,
+            amplitude,
+            phase: 0.0,
+        }
+    }
+
+    pub fn with_phase(mut self, phase: f32) -> Partial {
+        self.phase = phase;
+        self
+    }
+
+    pub fn sine(&self, fundamental: f32) -> SineWave {
+        SineWave::with_frequency(fundamental * self.ratio).with_offset(self.phase) * self.amplitude
+    }
+}
+
+/// A spectrum of partials relative to a fundamental, which can be turned into a [`Wave`] for any note.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Timbre {
+    partials: Vec<Partial>,
+}
+
+impl Timbre {
+    pub fn new(partials: Vec<Partial>) -> Timbre {
+        Timbre { partials }
+    }
+
+    /// Builds a timbre from `(amplitude, phase)` pairs for harmonics 1, 2, 3, ...
+    pub fn from_harmonics(harmonics: &[(f32, f32)]) -> Timbre {
+        Timbre::new(
+            harmonics
+                .iter()
+                .enumerate()
+                .filter(|(_, &(a, _))| a != 0.0)
+                .map(|(i, &(a, phase))| Partial::harmonic(i as u32 + 1, a).with_phase(phase))
+                .collect(),
+        )
+    }
+
+    pub fn partials(&self) -> &[Partial] {
+        &self.partials
+    }
+
+    pub fn add_partial(&mut self, partial: Partial) {
+        self.partials.push(partial);
+    }
+
+    /// Scales all partials so that their amplitudes sum to 1, which keeps the wave from clipping
+    pub fn normalized(mut self) -> Timbre {
+        let total: f32 = self.partials.iter().map(|p| p.amplitude.abs()).sum();
+        if total > 0.0 {
+            for partial in &mut self.partials {
+                partial.amplitude /= total;
+            }
+        }
+        self
+    }
+
+    pub fn wave(&self, note: Note) -> Wave {
+        self.wave_with_frequency(note.frequency())
+    }
+
+    pub fn wave_with_frequency(&self, fundamental: f32) -> Wave {
+        Wave::new(self.partials.iter().map(|p| p.sine(fundamental)).collect())
+    }
+
+    pub fn sine() -> Timbre {
+        Timbre::from_harmonics(&[(1.0, 0.0)])
+    }
+
+    /// Hammond style organ. Drawbars are given in the usual order
+    /// (16', 5 1/3', 8', 4', 2 2/3', 2', 1 3/5', 1 1/3', 1') with levels from 0 to 8
+    pub fn organ(drawbars: [u8; 9]) -> Timbre {
+        const RATIOS: [f32; 9] = [0.5, 1.5, 1.0, 2.0, 3.0, 4.0, 5.0, 6.0, 8.0];
+        Timbre::new(
+            RATIOS
+                .iter()
+                .zip(drawbars)
+                .filter(|(_, level)| *level > 0)
+                .map(|(&ratio, level)| {
+                    // Every drawbar step is 3dB
+                    let db = -3.0 * (8 - level.min(8)) as f32;
+                    Partial::inharmonic(ratio, 10f32.powf(db / 20.0))
+                })
+                .collect(),
+        )
+        .normalized()
+    }
+
+    /// Odd harmonics only, falling off with 1/n
+    pub fn clarinet() -> Timbre {
+        Timbre::new(
+            (1..=15)
+                .step_by(2)
+                .map(|n| Partial::harmonic(n, 1.0 / n as f32))
+                .collect(),
+        )
+        .normalized()
+    }
+
+    /// Band limited sawtooth, the classic bowed string spectrum
+    pub fn string() -> Timbre {
+        Timbre::new(
+            (1..=16)
+                .map(|n| Partial::harmonic(n, 1.0 / n as f32))
+                .collect(),
+        )
+        .normalized()
+    }
+
+    /// Inharmonic partials after Risset's bell
+    pub fn bell() -> Timbre {
+        const PARTIALS: [(f32, f32); 9] = [
+            (0.56, 1.0),
+            (0.92, 0.67),
+            (1.19, 1.0),
+            (1.71, 1.8),
+            (2.0, 2.67),
+            (2.74, 1.67),
+            (3.0, 1.46),
+            (3.76, 1.33),
+            (4.07, 1.33),
+        ];
+        Timbre::new(
+            PARTIALS
+                .iter()
+                .map(|&(ratio, amplitude)| Partial::inharmonic(ratio, amplitude))
+                .collect(),
+        )
+        .normalized()
+    }
+}
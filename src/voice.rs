@@ -0,0 +1,363 @@
+use crate::{Envelope, KarplusStrong, Note, SampleType, Samples, Timbre, Wave};
+
+/// A single sounding note
+pub trait Voice {
+    fn next_sample(&mut self) -> f32;
+
+    /// Starts the release phase. The voice keeps sounding until [`Voice::is_finished`]
+    fn release(&mut self);
+
+    fn is_finished(&self) -> bool;
+
+    /// Current loudness, used to pick the quietest voice to steal
+    fn level(&self) -> f32;
+}
+
+/// Anything that can start a [`Voice`] for a note
+pub trait Instrument {
+    type Voice: Voice;
+
+    /// `velocity` goes from 0 to 1
+    fn voice(&self, note: Note, velocity: f32, sample_rate: u32) -> Self::Voice;
+}
+
+/// Additive synth voice: a timbre shaped by an envelope
+#[derive(Clone, Debug, PartialEq)]
+pub struct Synth {
+    pub timbre: Timbre,
+    pub envelope: Envelope,
+}
+
+impl Synth {
+    pub fn new(timbre: Timbre, envelope: Envelope) -> Synth {
+        Synth { timbre, envelope }
+    }
+}
+
+impl Instrument for Synth {
+    type Voice = SynthVoice;
+
+    fn voice(&self, note: Note, velocity: f32, sample_rate: u32) -> SynthVoice {
+        SynthVoice {
+            wave: self.timbre.wave(note),
+            envelope: self.envelope,
+            velocity,
+            sample_rate,
+            position: 0,
+            released: None,
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct SynthVoice {
+    wave: Wave,
+    envelope: Envelope,
+    velocity: f32,
+    sample_rate: u32,
+    /// Samples played so far
+    position: u64,
+    released: Option<f32>,
+}
+
+impl SynthVoice {
+    /// Seconds since the note started, computed from the sample count so it never drifts
+    fn time(&self) -> f32 {
+        (self.position as f64 / self.sample_rate as f64) as f32
+    }
+}
+
+impl Voice for SynthVoice {
+    fn next_sample(&mut self) -> f32 {
+        let value = self.level() * self.wave.at(self.time());
+        self.position += 1;
+        value
+    }
+
+    fn release(&mut self) {
+        let time = self.time();
+        self.released.get_or_insert(time);
+    }
+
+    fn is_finished(&self) -> bool {
+        let time = self.time();
+        match self.released {
+            Some(released) => time >= self.envelope.length(released),
+            None => self.envelope.sustain == 0.0 && time >= self.envelope.length(f32::MAX),
+        }
+    }
+
+    fn level(&self) -> f32 {
+        self.velocity * self.envelope.at(self.time(), self.released)
+    }
+}
+
+/// Plays back pre-rendered samples, fading out quickly when released
+#[derive(Clone, Debug, PartialEq)]
+pub struct OneShot {
+    points: Vec<f32>,
+    position: usize,
+    fade: usize,
+    released: Option<usize>,
+    ignore_release: bool,
+}
+
+impl OneShot {
+    const FADE: f32 = 0.01;
+
+    pub fn new(samples: &Samples) -> OneShot {
+        let points = (0..samples.sample_count().unwrap_or_default())
+            .map(|i| samples.sample_f32(i).unwrap())
+            .collect();
+        OneShot {
+            points,
+            position: 0,
+            fade: (samples.sample_rate() as f32 * Self::FADE) as usize,
+            released: None,
+            ignore_release: false,
+        }
+    }
+
+    /// Keeps playing to the end after the note is released, like a drum hit
+    pub fn ignoring_release(mut self) -> OneShot {
+        self.ignore_release = true;
+        self
+    }
+
+    fn gain(&self) -> f32 {
+        match self.released {
+            Some(released) => 1.0 - (self.position - released) as f32 / self.fade.max(1) as f32,
+            None => 1.0,
+        }
+    }
+}
+
+impl Voice for OneShot {
+    fn next_sample(&mut self) -> f32 {
+        if self.is_finished() {
+            return 0.0;
+        }
+        let value = self.gain() * self.points[self.position];
+        self.position += 1;
+        value
+    }
+
+    fn release(&mut self) {
+        if !self.ignore_release {
+            self.released.get_or_insert(self.position);
+        }
+    }
+
+    fn is_finished(&self) -> bool {
+        self.position >= self.points.len() || self.gain() <= 0.0
+    }
+
+    fn level(&self) -> f32 {
+        let window = &self.points[self.position.min(self.points.len())..];
+        self.gain() * window.iter().take(64).fold(0.0f32, |a, x| a.max(x.abs()))
+    }
+}
+
+impl Instrument for KarplusStrong {
+    type Voice = OneShot;
+
+    /// Plucks a string with the settings of `self`, tuned to `note`
+    fn voice(&self, note: Note, velocity: f32, sample_rate: u32) -> OneShot {
+        let string = KarplusStrong::new(note)
+            .with_amplitude(self.amplitude * velocity)
+            .with_decay(self.decay)
+            .with_brightness(self.brightness)
+            .with_seed(self.seed);
+        OneShot::new(&string.render(sample_rate, self.decay))
+    }
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum StealPolicy {
+    /// Drop new notes when every voice is busy
+    None,
+    Oldest,
+    Quietest,
+}
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum VoiceEvent {
+    NoteOn(Note, f32),
+    NoteOff(Note),
+    AllNotesOff,
+}
+
+struct ActiveVoice<V> {
+    note: Note,
+    voice: V,
+    started: u64,
+    released: bool,
+}
+
+/// Plays an instrument polyphonically with a bounded number of voices
+pub struct VoiceManager<I: Instrument> {
+    instrument: I,
+    sample_rate: u32,
+    max_voices: usize,
+    pub policy: StealPolicy,
+    voices: Vec<ActiveVoice<I::Voice>>,
+    started: u64,
+}
+
+impl<I: Instrument> VoiceManager<I> {
+    /// # Panics
+    /// Panics if `max_voices` is 0
+    #[track_caller]
+    pub fn new(instrument: I, sample_rate: u32, max_voices: usize) -> Self {
+        assert!(max_voices > 0, "A voice manager needs at least one voice");
+        Self {
+            instrument,
+            sample_rate,
+            max_voices,
+            policy: StealPolicy::Oldest,
+            voices: Vec::with_capacity(max_voices),
+            started: 0,
+        }
+    }
+
+    pub fn with_policy(mut self, policy: StealPolicy) -> Self {
+        self.policy = policy;
+        self
+    }
+
+    pub fn instrument(&self) -> &I {
+        &self.instrument
+    }
+
+    pub fn sample_rate(&self) -> u32 {
+        self.sample_rate
+    }
+
+    pub fn max_voices(&self) -> usize {
+        self.max_voices
+    }
+
+    pub fn active_voices(&self) -> usize {
+        self.voices.len()
+    }
+
+    pub fn handle(&mut self, event: VoiceEvent) {
+        match event {
+            VoiceEvent::NoteOn(note, velocity) => self.note_on(note, velocity),
+            VoiceEvent::NoteOff(note) => self.note_off(note),
+            VoiceEvent::AllNotesOff => self.all_notes_off(),
+        }
+    }
+
+    /// Starts `note`, releasing it first if it is already held
+    pub fn note_on(&mut self, note: Note, velocity: f32) {
+        self.note_off(note);
+        if self.voices.len() >= self.max_voices {
+            // Voices that are already fading out are stolen before held ones
+            let victim = match self.policy {
+                StealPolicy::None => return,
+                StealPolicy::Oldest => self
+                    .voices
+                    .iter()
+                    .enumerate()
+                    .min_by_key(|(_, v)| (!v.released, v.started))
+                    .map(|(i, _)| i),
+                StealPolicy::Quietest => self
+                    .voices
+                    .iter()
+                    .enumerate()
+                    .min_by(|(_, a), (_, b)| {
+                        (!a.released, a.voice.level())
+                            .partial_cmp(&(!b.released, b.voice.level()))
+                            .unwrap_or(std::cmp::Ordering::Equal)
+                    })
+                    .map(|(i, _)| i),
+            };
+            if let Some(i) = victim {
+                self.voices.swap_remove(i);
+            }
+        }
+
+        self.started += 1;
+        self.voices.push(ActiveVoice {
+            note,
+            voice: self.instrument.voice(note, velocity, self.sample_rate),
+            started: self.started,
+            released: false,
+        });
+    }
+
+    pub fn note_off(&mut self, note: Note) {
+        for active in self
+            .voices
+            .iter_mut()
+            .filter(|v| v.note == note && !v.released)
+        {
+            active.voice.release();
+            active.released = true;
+        }
+    }
+
+    pub fn all_notes_off(&mut self) {
+        for active in &mut self.voices {
+            active.voice.release();
+            active.released = true;
+        }
+    }
+
+    pub fn next_sample(&mut self) -> f32 {
+        let value = self.voices.iter_mut().map(|v| v.voice.next_sample()).sum();
+        self.voices.retain(|v| !v.voice.is_finished());
+        value
+    }
+
+    /// Fills `block` with the sum of all voices
+    pub fn render_block(&mut self, block: &mut [f32]) {
+        for x in block.iter_mut() {
+            *x = 0.0;
+        }
+        for active in &mut self.voices {
+            for x in block.iter_mut() {
+                *x += active.voice.next_sample();
+            }
+        }
+        self.voices.retain(|v| !v.voice.is_finished());
+    }
+
+    /// Renders `count` samples, applying every event at its sample index.
+    /// Events must be sorted by index
+    pub fn render(&mut self, events: &[(usize, VoiceEvent)], count: usize) -> Samples {
+        let mut points = vec![0.0; count];
+        let mut position = 0;
+        for &(index, event) in events {
+            let index = index.clamp(position, count);
+            self.render_block(&mut points[position..index]);
+            position = index;
+            self.handle(event);
+        }
+        self.render_block(&mut points[position..]);
+        Samples::new(SampleType::Pointsf32(points)).with_sample_rate(self.sample_rate)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::A4;
+
+    #[test]
+    fn long_notes_keep_their_pitch() {
+        let sample_rate = 44100;
+        let synth = Synth::new(Timbre::sine(), Envelope::new(0.01, 0.0, 1.0, 0.1));
+        let mut voice = synth.voice(A4, 1.0, sample_rate);
+        let start = 12 * sample_rate as usize;
+        for _ in 0..start {
+            voice.next_sample();
+        }
+        for i in start..start + 1000 {
+            let x = i as f64 / sample_rate as f64;
+            let expected = (std::f64::consts::TAU * 440.0 * x).sin();
+            assert!((voice.next_sample() as f64 - expected).abs() < 0.01);
+        }
+    }
+}
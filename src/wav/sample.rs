@@ -33,9 +33,22 @@ impl Samples {
         }
     }
 
+    /// Like [`Samples::sample`], but without quantizing to 16 bits
+    pub fn sample_f32(&self, i: usize) -> Option<f32> {
+        match &self.samples {
+            SampleType::Wave(wave) => Some(wave.at(i as f32 / self.sample_rate)),
+            SampleType::Pointsi16(points) => points.get(i).map(|&x| x as f32 / Self::MAX_AMPLITUDE),
+            SampleType::Pointsf32(points) => points.get(i).copied(),
+        }
+    }
+
     pub fn sample_rate(&self) -> u32 {
         self.sample_rate as u32
     }
+
+    pub fn sample_count(&self) -> Option<usize> {
+        self.samples.sample_count()
+    }
 }
 
 #[derive(Clone, PartialEq, Debug)]
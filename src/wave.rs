@@ -1,6 +1,10 @@
-use std::{f32::consts::TAU, fmt::Debug};
+use std::{
+    f32::consts::{FRAC_PI_2, PI, TAU},
+    fmt::Debug,
+    ops::{Add, Mul, Neg},
+};
 
-use crate::Note;
+use crate::{Note, SampleType, Samples, Timbre};
 
 #[derive(Clone, PartialEq)]
 pub struct Wave {
@@ -8,10 +12,18 @@ pub struct Wave {
 }
 
 impl Wave {
+    /// Denominator limit used when a period is needed without one being given
+    pub const MAX_DENOMINATOR: u32 = 1000;
+
     pub fn new(waves: Vec<SineWave>) -> Self {
         Self { waves }
     }
 
+    /// Builds a wave from the fundamental of `note` and `(amplitude, phase)` pairs for harmonics 1, 2, 3, ...
+    pub fn from_harmonics(note: Note, harmonics: &[(f32, f32)]) -> Self {
+        Timbre::from_harmonics(harmonics).wave(note)
+    }
+
     pub fn add_wave(&mut self, wave: SineWave) {
         self.waves.push(wave);
     }
@@ -27,14 +39,67 @@ impl Wave {
         self.waves.extend(other.waves)
     }
 
+    /// Frequency of the lowest partial. See [`Wave::fundamental`] for the frequency the whole wave repeats at
     pub fn frequency(&self) -> f32 {
         self.waves
             .iter()
-            .min_by_key(|n| n.frequency as u32)
             .map(|n| n.frequency)
+            .min_by(|a, b| a.total_cmp(b))
             .unwrap_or_default()
     }
 
+    /// Largest frequency that every partial is a whole multiple of.
+    /// Frequencies that are fractions with denominators up to `max_denominator` (like every note's)
+    /// give the exact answer. Otherwise the ratios to the lowest partial are approximated by such fractions.
+    /// Returns `None` if there are no partials other than DC, or the common period is too long to represent
+    pub fn fundamental(&self, max_denominator: u32) -> Option<f32> {
+        let max_denominator = max_denominator as u64;
+        let frequencies: Vec<f64> = self
+            .waves
+            .iter()
+            .map(|wave| (wave.freq_comp as f64 / TAU as f64).abs())
+            .filter(|&f| f > 1e-9)
+            .collect();
+        let reference = frequencies.iter().copied().reduce(f64::min)?;
+
+        let exact: Option<Vec<(u64, u64)>> = frequencies
+            .iter()
+            .map(|&f| match rational_approximation(f, max_denominator) {
+                (fraction, true) => Some(fraction),
+                _ => None,
+            })
+            .collect();
+        if let Some(fundamental) = exact.and_then(|fractions| common_divisor(&fractions)) {
+            return Some(fundamental as f32);
+        }
+
+        let ratios: Vec<(u64, u64)> = frequencies
+            .iter()
+            .map(|&f| rational_approximation(f / reference, max_denominator).0)
+            .collect();
+        common_divisor(&ratios).map(|ratio| (reference * ratio) as f32)
+    }
+
+    /// Time after which the wave repeats exactly, see [`Wave::fundamental`]
+    pub fn period(&self, max_denominator: u32) -> Option<f32> {
+        self.fundamental(max_denominator).map(|f| 1.0 / f)
+    }
+
+    /// Renders `cycles` periods of the wave so that the result can be looped without a click.
+    /// Time is stretched very slightly so that the loop is a whole number of samples long.
+    /// Returns `None` if the wave has no period or the loop would be longer than `max_seconds`
+    pub fn render_loop(&self, sample_rate: u32, cycles: u32, max_seconds: f32) -> Option<Samples> {
+        let length = self.period(Self::MAX_DENOMINATOR)? as f64 * cycles.max(1) as f64;
+        if length > max_seconds as f64 {
+            return None;
+        }
+        let count = ((length * sample_rate as f64).round() as usize).max(1);
+        let points = (0..count)
+            .map(|i| self.at((i as f64 * length / count as f64) as f32))
+            .collect();
+        Some(Samples::new(SampleType::Pointsf32(points)).with_sample_rate(sample_rate))
+    }
+
     pub fn waves(&self) -> &[SineWave] {
         &self.waves
     }
@@ -42,6 +107,206 @@ impl Wave {
     pub fn at(&self, x: f32) -> f32 {
         self.waves.iter().map(|wave| wave.at(x)).sum()
     }
+
+    /// Exact spectrum of the wave, one line per distinct frequency, sorted by frequency
+    pub fn spectrum(&self) -> Vec<SpectralLine> {
+        self.simplify()
+            .waves
+            .iter()
+            .map(|wave| SpectralLine {
+                frequency: wave.frequency(),
+                magnitude: wave.amplitude,
+                phase: -wave.offset,
+            })
+            .collect()
+    }
+
+    /// Mean of the squared wave over a long time
+    pub fn power(&self) -> f32 {
+        self.spectrum()
+            .iter()
+            .map(|line| {
+                if line.frequency == 0.0 {
+                    // A constant of A sin(phase)
+                    (line.magnitude * line.phase.sin()).powi(2)
+                } else {
+                    line.magnitude * line.magnitude / 2.0
+                }
+            })
+            .sum()
+    }
+
+    pub fn rms(&self) -> f32 {
+        self.power().sqrt()
+    }
+
+    /// Merges partials of equal frequency into one, by adding them as phasors.
+    /// Partials that cancel out are dropped and the rest are sorted by frequency
+    pub fn simplify(&self) -> Wave {
+        let mut partials: Vec<(f32, f32, f32)> = self
+            .waves
+            .iter()
+            .map(|wave| {
+                let wave = wave.normalized();
+                // A sin(wx - o) = A cos(o) sin(wx) - A sin(o) cos(wx)
+                let (sin, cos) = wave.offset.sin_cos();
+                (wave.freq_comp, wave.amplitude * cos, wave.amplitude * sin)
+            })
+            .collect();
+        partials.sort_by(|a, b| a.0.total_cmp(&b.0));
+
+        let mut merged: Vec<(f32, f32, f32)> = Vec::with_capacity(partials.len());
+        for (freq_comp, c, s) in partials {
+            match merged.last_mut() {
+                Some(last) if (last.0 - freq_comp).abs() <= 1e-6 * freq_comp.abs().max(1.0) => {
+                    last.1 += c;
+                    last.2 += s;
+                }
+                _ => merged.push((freq_comp, c, s)),
+            }
+        }
+
+        Wave::new(
+            merged
+                .into_iter()
+                .filter(|(_, c, s)| c.hypot(*s) > 1e-6)
+                .map(|(freq_comp, c, s)| {
+                    SineWave::new(freq_comp).with_offset(s.atan2(c)) * c.hypot(s)
+                })
+                .collect(),
+        )
+    }
+}
+
+/// One partial of a spectrum: `magnitude * sin(TAU * frequency * x + phase)`
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct SpectralLine {
+    pub frequency: f32,
+    pub magnitude: f32,
+    pub phase: f32,
+}
+
+/// Best approximation of `x` by `p / q` with `q <= max_denominator`, from its continued fraction,
+/// and whether it matches `x` to within float precision
+fn rational_approximation(x: f64, max_denominator: u64) -> ((u64, u64), bool) {
+    let (mut p0, mut q0, mut p1, mut q1) = (0u64, 1u64, 1u64, 0u64);
+    let mut rest = x;
+    let mut exact = false;
+    loop {
+        let a = rest.floor() as u64;
+        let next = a
+            .checked_mul(p1)
+            .and_then(|p| p.checked_add(p0))
+            .zip(a.checked_mul(q1).and_then(|q| q.checked_add(q0)));
+        let Some((p2, q2)) = next.filter(|&(_, q)| q <= max_denominator.max(1)) else {
+            break;
+        };
+        (p0, q0, p1, q1) = (p1, q1, p2, q2);
+        // Partials are f32, so anything closer than a couple of rounding steps is exact
+        exact = (x - p1 as f64 / q1 as f64).abs() <= 2.0 * f32::EPSILON as f64 * x;
+        let error = rest.fract();
+        if exact || error < 1e-12 {
+            break;
+        }
+        rest = 1.0 / error;
+    }
+    ((p1.max(1), q1.max(1)), exact)
+}
+
+/// Largest number that every fraction is a whole multiple of, `None` if it can't be represented
+fn common_divisor(fractions: &[(u64, u64)]) -> Option<f64> {
+    // All of them are multiples of 1 / lcm(q), so it is gcd(p * lcm(q) / q) / lcm(q)
+    let denominator = fractions.iter().try_fold(1, |l, &(_, q)| lcm(l, q))?;
+    let numerator = fractions.iter().try_fold(0, |g, &(p, q)| {
+        p.checked_mul(denominator / q).map(|n| gcd(g, n))
+    })?;
+    Some(numerator as f64 / denominator as f64)
+}
+
+fn gcd(a: u64, b: u64) -> u64 {
+    if b == 0 {
+        a
+    } else {
+        gcd(b, a % b)
+    }
+}
+
+fn lcm(a: u64, b: u64) -> Option<u64> {
+    (a / gcd(a, b)).checked_mul(b)
+}
+
+impl Add for Wave {
+    type Output = Wave;
+
+    fn add(mut self, other: Wave) -> Wave {
+        self.waves.extend(other.waves);
+        self
+    }
+}
+
+impl Add<SineWave> for Wave {
+    type Output = Wave;
+
+    fn add(mut self, other: SineWave) -> Wave {
+        self.waves.push(other);
+        self
+    }
+}
+
+impl Mul<f32> for Wave {
+    type Output = Wave;
+
+    fn mul(mut self, scale: f32) -> Wave {
+        for wave in &mut self.waves {
+            wave.amplitude *= scale;
+        }
+        self
+    }
+}
+
+impl Mul<Wave> for f32 {
+    type Output = Wave;
+
+    fn mul(self, wave: Wave) -> Wave {
+        wave * self
+    }
+}
+
+/// Ring modulation, expanded into a sum of sines with the product-to-sum identity
+impl Mul for Wave {
+    type Output = Wave;
+
+    fn mul(self, other: Wave) -> Wave {
+        let mut product = Wave::new(Vec::new());
+        for a in &self.waves {
+            for b in &other.waves {
+                product = product + (*a * *b);
+            }
+        }
+        product
+    }
+}
+
+impl Mul<SineWave> for Wave {
+    type Output = Wave;
+
+    fn mul(self, other: SineWave) -> Wave {
+        self * Wave::from(other)
+    }
+}
+
+impl Neg for Wave {
+    type Output = Wave;
+
+    fn neg(self) -> Wave {
+        self * -1.0
+    }
+}
+
+impl From<SineWave> for Wave {
+    fn from(wave: SineWave) -> Wave {
+        Wave::new(vec![wave])
+    }
 }
 
 impl Debug for Wave {
@@ -84,8 +349,11 @@ impl SineWave {
         }
     }
 
+    /// # Panics
+    /// Panics if amplitude is not between 0 and 1
+    #[track_caller]
     pub fn with_amplitude(mut self, amplitude: f32) -> SineWave {
-        assert!(!(0.0..=1.0).contains(&amplitude));
+        assert!((0.0..=1.0).contains(&amplitude), "Invalid amplitude");
         self.amplitude = amplitude;
         self
     }
@@ -111,6 +379,86 @@ impl SineWave {
     pub fn frequency(&self) -> f32 {
         self.frequency
     }
+
+    /// The same wave with a non-negative frequency and amplitude
+    fn normalized(mut self) -> SineWave {
+        // sin(-wx - o) = sin(wx + o + PI)
+        if self.freq_comp < 0.0 {
+            self = SineWave::new(-self.freq_comp).with_offset(-self.offset - PI) * self.amplitude;
+        }
+        if self.amplitude < 0.0 {
+            self.amplitude = -self.amplitude;
+            self.offset += PI;
+        }
+        self.offset = (self.offset + PI).rem_euclid(TAU) - PI;
+        self
+    }
+}
+
+impl Add for SineWave {
+    type Output = Wave;
+
+    fn add(self, other: SineWave) -> Wave {
+        Wave::new(vec![self, other])
+    }
+}
+
+impl Add<Wave> for SineWave {
+    type Output = Wave;
+
+    fn add(self, other: Wave) -> Wave {
+        Wave::from(self) + other
+    }
+}
+
+impl Mul<f32> for SineWave {
+    type Output = SineWave;
+
+    fn mul(mut self, scale: f32) -> SineWave {
+        self.amplitude *= scale;
+        self
+    }
+}
+
+impl Mul<SineWave> for f32 {
+    type Output = SineWave;
+
+    fn mul(self, wave: SineWave) -> SineWave {
+        wave * self
+    }
+}
+
+/// Ring modulation: sin(a)sin(b) = (cos(a - b) - cos(a + b)) / 2
+impl Mul for SineWave {
+    type Output = Wave;
+
+    fn mul(self, other: SineWave) -> Wave {
+        let amplitude = self.amplitude * other.amplitude / 2.0;
+        // cos(t) = sin(t + PI/2) and -cos(t) = sin(t - PI/2)
+        let difference = SineWave::new(self.freq_comp - other.freq_comp)
+            .with_offset(self.offset - other.offset - FRAC_PI_2)
+            * amplitude;
+        let sum = SineWave::new(self.freq_comp + other.freq_comp)
+            .with_offset(self.offset + other.offset + FRAC_PI_2)
+            * amplitude;
+        Wave::new(vec![difference.normalized(), sum.normalized()])
+    }
+}
+
+impl Mul<Wave> for SineWave {
+    type Output = Wave;
+
+    fn mul(self, other: Wave) -> Wave {
+        Wave::from(self) * other
+    }
+}
+
+impl Neg for SineWave {
+    type Output = SineWave;
+
+    fn neg(self) -> SineWave {
+        self * -1.0
+    }
 }
 
 impl Debug for SineWave {
@@ -130,3 +478,60 @@ impl Debug for SineWave {
         Ok(())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Db5, Partial, Timbre, A4, C4, E4, E5, G4};
+
+    #[test]
+    fn triad_periods_are_exact() {
+        let string = Timbre::string();
+        let triad = string.wave(A4) + string.wave(Db5) + string.wave(E5);
+        assert_eq!(triad.period(Wave::MAX_DENOMINATOR), Some(3.125));
+
+        let clarinet = Timbre::clarinet();
+        let triad = clarinet.wave(C4) + clarinet.wave(E4) + clarinet.wave(G4);
+        assert_eq!(triad.period(Wave::MAX_DENOMINATOR), Some(1.25));
+    }
+
+    #[test]
+    fn partials_keep_any_amplitude() {
+        let timbre = Timbre::new(vec![Partial::harmonic(1, 2.0), Partial::harmonic(2, -0.5)]);
+        let amplitudes: Vec<f32> = timbre
+            .wave(A4)
+            .waves()
+            .iter()
+            .map(|w| w.amplitude)
+            .collect();
+        assert_eq!(amplitudes, [2.0, -0.5]);
+        assert_eq!(
+            SineWave::with_frequency(440.0)
+                .with_amplitude(0.5)
+                .amplitude,
+            0.5
+        );
+    }
+
+    #[test]
+    fn render_loop_respects_max_length() {
+        let string = Timbre::string();
+        let triad = string.wave(A4) + string.wave(Db5) + string.wave(E5);
+        assert!(triad.render_loop(44100, 1, 1.0).is_none());
+        let samples = triad.render_loop(44100, 1, 5.0).unwrap();
+        assert_eq!(samples.sample_count(), Some(137813));
+    }
+
+    #[test]
+    fn unrelated_partials_do_not_overflow() {
+        let wave = Wave::from(SineWave::with_frequency(19999.37))
+            + SineWave::with_frequency(7.123457)
+            + SineWave::with_frequency(13.0001);
+        // The ratios to 7.123457 are 1, 11/6 and 5615/2 with denominators up to 10
+        let fundamental = wave.fundamental(10).unwrap();
+        assert!((fundamental - 7.123457 / 6.0).abs() < 1e-5);
+        let fundamental = wave.fundamental(Wave::MAX_DENOMINATOR).unwrap();
+        let cycles = 7.123457 / fundamental;
+        assert!((cycles - cycles.round()).abs() < 1e-2);
+    }
+}
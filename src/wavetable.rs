@@ -0,0 +1,190 @@
+use std::f32::consts::TAU;
+
+use crate::{Note, SampleType, Samples, Wave};
+
+/// A single cycle waveform together with band limited copies of it, one per octave
+#[derive(Clone, Debug, PartialEq)]
+pub struct Wavetable {
+    mips: Vec<Vec<f32>>,
+}
+
+impl Wavetable {
+    /// # Panics
+    /// Panics if `cycle` is empty
+    #[track_caller]
+    pub fn new(cycle: Vec<f32>) -> Wavetable {
+        assert!(!cycle.is_empty(), "Empty wavetable");
+        let len = cycle.len();
+        let (cos, sin) = dft(&cycle);
+
+        let mut mips = vec![cycle];
+        let mut harmonics = len / 2;
+        while harmonics > 1 {
+            harmonics /= 2;
+            mips.push(
+                (0..len)
+                    .map(|n| {
+                        let mut x = cos[0] / len as f32;
+                        for k in 1..=harmonics {
+                            let w = TAU * (k * n % len) as f32 / len as f32;
+                            x += 2.0 * (cos[k] * w.cos() + sin[k] * w.sin()) / len as f32;
+                        }
+                        x
+                    })
+                    .collect(),
+            );
+        }
+
+        Wavetable { mips }
+    }
+
+    /// Uses every sample of `samples` as one cycle.
+    /// Returns `None` for analytic samples, which have no fixed length
+    pub fn from_samples(samples: &Samples) -> Option<Wavetable> {
+        let count = samples.sample_count()?;
+        let cycle = (0..count)
+            .map(|i| samples.sample(i).unwrap() as f32 / Samples::MAX_AMPLITUDE)
+            .collect();
+        Some(Wavetable::new(cycle))
+    }
+
+    /// Samples one period of `wave` into a table of `size` points, at least one.
+    /// Returns `None` if the wave has no period (see [`Wave::period`]) or it is longer than `max_period` seconds
+    pub fn from_wave(wave: &Wave, size: usize, max_period: f32) -> Option<Wavetable> {
+        let period = wave.period(Wave::MAX_DENOMINATOR)?;
+        if period > max_period {
+            return None;
+        }
+        let size = size.max(1);
+        let cycle = (0..size)
+            .map(|i| wave.at(i as f32 * period / size as f32))
+            .collect();
+        Some(Wavetable::new(cycle))
+    }
+
+    pub fn cycle_len(&self) -> usize {
+        self.mips[0].len()
+    }
+
+    /// Reads the table at `phase` (in cycles), choosing the copy that does not alias at `frequency`
+    pub fn at(&self, phase: f32, frequency: f32, sample_rate: u32) -> f32 {
+        let table = &self.mips[self.level(frequency, sample_rate)];
+        let len = table.len();
+        let position = phase.rem_euclid(1.0) * len as f32;
+        let i = position as usize % len;
+        let frac = position.fract();
+        table[i] + (table[(i + 1) % len] - table[i]) * frac
+    }
+
+    fn level(&self, frequency: f32, sample_rate: u32) -> usize {
+        let allowed = sample_rate as f32 / 2.0 / frequency.abs().max(f32::EPSILON);
+        let mut harmonics = self.cycle_len() / 2;
+        let mut level = 0;
+        while harmonics as f32 > allowed && level + 1 < self.mips.len() {
+            harmonics /= 2;
+            level += 1;
+        }
+        level
+    }
+}
+
+fn dft(x: &[f32]) -> (Vec<f32>, Vec<f32>) {
+    let len = x.len();
+    (0..=len / 2)
+        .map(|k| {
+            x.iter().enumerate().fold((0.0, 0.0), |(c, s), (n, &x)| {
+                let w = TAU * (k * n % len) as f32 / len as f32;
+                (c + x * w.cos(), s + x * w.sin())
+            })
+        })
+        .unzip()
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct WavetableOscillator {
+    tables: Vec<Wavetable>,
+    frequency: f32,
+    pub amplitude: f32,
+    morph: (f32, f32),
+}
+
+impl WavetableOscillator {
+    pub fn new(table: Wavetable, note: Note) -> WavetableOscillator {
+        WavetableOscillator::with_tables(vec![table], note)
+    }
+
+    /// # Panics
+    /// Panics if `tables` is empty
+    #[track_caller]
+    pub fn with_tables(tables: Vec<Wavetable>, note: Note) -> WavetableOscillator {
+        assert!(!tables.is_empty(), "No wavetables");
+        WavetableOscillator {
+            tables,
+            frequency: note.frequency(),
+            amplitude: note.amplitude(),
+            morph: (0.0, 0.0),
+        }
+    }
+
+    pub fn with_frequency(mut self, frequency: f32) -> WavetableOscillator {
+        self.frequency = frequency;
+        self
+    }
+
+    /// Sweeps the table position from `start` to `end` over the rendered length.
+    /// Position 1.5 is halfway between the second and third table
+    pub fn with_morph(mut self, start: f32, end: f32) -> WavetableOscillator {
+        let last = (self.tables.len() - 1) as f32;
+        self.morph = (start.clamp(0.0, last), end.clamp(0.0, last));
+        self
+    }
+
+    pub fn frequency(&self) -> f32 {
+        self.frequency
+    }
+
+    /// Value at `phase` and table `position`, which is clamped to the tables there are
+    pub fn at(&self, phase: f32, position: f32, sample_rate: u32) -> f32 {
+        let position = position.clamp(0.0, (self.tables.len() - 1) as f32);
+        let i = position as usize;
+        let frac = position.fract();
+        let a = self.tables[i].at(phase, self.frequency, sample_rate);
+        let value = match self.tables.get(i + 1) {
+            Some(next) if frac > 0.0 => {
+                a + (next.at(phase, self.frequency, sample_rate) - a) * frac
+            }
+            _ => a,
+        };
+        self.amplitude * value
+    }
+
+    pub fn render(&self, sample_rate: u32, seconds: f32) -> Samples {
+        let count = (sample_rate as f32 * seconds) as usize;
+        let step = self.frequency / sample_rate as f32;
+        let (start, end) = self.morph;
+        let mut phase = 0.0;
+        let points = (0..count)
+            .map(|i| {
+                let position = start + (end - start) * i as f32 / count as f32;
+                let x = self.at(phase, position, sample_rate);
+                phase = (phase + step).fract();
+                x
+            })
+            .collect();
+        Samples::new(SampleType::Pointsf32(points)).with_sample_rate(sample_rate)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::SineWave;
+
+    #[test]
+    fn empty_tables_hold_one_point() {
+        let wave = Wave::from(SineWave::with_frequency(440.0));
+        let table = Wavetable::from_wave(&wave, 0, 1.0).unwrap();
+        assert_eq!(table.cycle_len(), 1);
+        assert_eq!(table.at(0.5, 440.0, 44100), 0.0);
+    }
+}